@@ -0,0 +1,95 @@
+//! Where the todo list lives: the config file and data path resolution.
+
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::store::Backend;
+use crate::Error;
+
+/// Directory name used under the XDG config/data directories.
+const APP_NAME: &str = "cli-todo-rust";
+
+/// Optional user settings, read from `$XDG_CONFIG_HOME/cli-todo-rust/config.json`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Location of the todo file. Relative paths are resolved against the
+    /// directory containing the config file; a leading `~/` means $HOME.
+    pub file: Option<PathBuf>,
+    /// Storage backend to keep the todos in.
+    #[serde(default)]
+    pub backend: Backend,
+    /// Directory the config file was read from (not part of the file itself).
+    #[serde(skip)]
+    dir: Option<PathBuf>,
+}
+
+/// Look up an XDG base directory, falling back to `$HOME/<fallback>` when the
+/// variable is unset or empty (as the XDG spec requires).
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    match env::var_os(var) {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)),
+    }
+}
+
+/// Expand a leading `~/` to the user's home directory.
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path,
+    }
+}
+
+/// Read the config file if there is one. A missing file is not an error.
+pub fn load_config() -> Result<Config, Error> {
+    let Some(dir) = xdg_dir("XDG_CONFIG_HOME", ".config").map(|d| d.join(APP_NAME)) else {
+        return Ok(Config::default());
+    };
+    let path = dir.join("config.json");
+    let mut config: Config = if path.exists() {
+        let data = fs::read_to_string(&path)?;
+        serde_json::from_str(&data)
+            .map_err(|e| Error::Parse(format!("invalid config file {}: {}", path.display(), e)))?
+    } else {
+        Config::default()
+    };
+    config.dir = Some(dir);
+    Ok(config)
+}
+
+/// Work out which todo file to use. The first match wins:
+///
+/// 1. `flag`, an explicit path such as the CLI's `--file` option
+/// 2. the `TODO_FILE` environment variable
+/// 3. `file` in the config file
+/// 4. `$XDG_DATA_HOME/cli-todo-rust/todos.json` (default `~/.local/share/...`),
+///    or `todos.db` / `todos.events.jsonl` with the SQLite / events backends
+///
+/// This is the only place that knows where the data lives; the stores
+/// just take the resolved path.
+pub fn data_file_path(flag: Option<PathBuf>, config: &Config) -> Result<PathBuf, Error> {
+    if let Some(path) = flag {
+        return Ok(path);
+    }
+    match env::var_os("TODO_FILE") {
+        Some(path) if !path.is_empty() => return Ok(PathBuf::from(path)),
+        _ => {}
+    }
+
+    if let Some(path) = &config.file {
+        let path = expand_home(path.clone());
+        return Ok(match &config.dir {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path,
+        });
+    }
+
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+        .map(|dir| dir.join(APP_NAME).join(config.backend.default_file_name()))
+        .ok_or_else(|| {
+            Error::Validation("cannot locate the data directory: set HOME, XDG_DATA_HOME or TODO_FILE".to_string())
+        })
+}
@@ -0,0 +1,277 @@
+//! Reading and writing CSV (RFC 4180) and TSV spreadsheets of todos.
+//!
+//! Each row is a task and each column one [`Field`]. Exports choose their
+//! columns; imports match the header row against the field names (ignoring
+//! case, spaces and dashes), plus any mapping of other header names onto
+//! fields given by the caller. Columns that match no field are ignored.
+//!
+//! Tags are written space-separated in one cell; empty cells are unset
+//! fields.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Date, Error, Status, Timestamp, Todo};
+
+/// A todo field that can be a column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// `id`; ignored on import, where tasks get new IDs.
+    Id,
+    /// `description`; the only column an import needs.
+    Description,
+    /// `status`, e.g. `in-progress`; `todo` if empty.
+    Status,
+    /// `priority`: `H`, `M` or `L` (or `high`...).
+    Priority,
+    /// `due`: a date, or a phrase like `tomorrow` on import.
+    Due,
+    /// `tags`, separated by spaces or commas.
+    Tags,
+    /// `project`.
+    Project,
+    /// `created_at`, RFC 3339.
+    CreatedAt,
+    /// `updated_at`, RFC 3339.
+    UpdatedAt,
+    /// `completed_at`, RFC 3339.
+    CompletedAt,
+}
+
+impl Field {
+    /// Every field, in the order exports use by default.
+    pub const ALL: [Field; 10] = [
+        Field::Id,
+        Field::Description,
+        Field::Status,
+        Field::Priority,
+        Field::Due,
+        Field::Tags,
+        Field::Project,
+        Field::CreatedAt,
+        Field::UpdatedAt,
+        Field::CompletedAt,
+    ];
+
+    /// The column name, e.g. `created_at`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::Description => "description",
+            Field::Status => "status",
+            Field::Priority => "priority",
+            Field::Due => "due",
+            Field::Tags => "tags",
+            Field::Project => "project",
+            Field::CreatedAt => "created_at",
+            Field::UpdatedAt => "updated_at",
+            Field::CompletedAt => "completed_at",
+        }
+    }
+
+    /// The field's value in `todo`, as a cell.
+    pub fn value(self, todo: &Todo) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        match self {
+            Field::Id => todo.id.to_string(),
+            Field::Description => todo.description.clone(),
+            Field::Status => todo.status.to_string(),
+            Field::Priority => optional(todo.priority.map(|priority| priority.to_string())),
+            Field::Due => optional(todo.due.map(|due| due.to_string())),
+            Field::Tags => todo.tags.join(" "),
+            Field::Project => optional(todo.project.clone()),
+            Field::CreatedAt => optional(todo.created_at.map(|t| t.to_string())),
+            Field::UpdatedAt => optional(todo.updated_at.map(|t| t.to_string())),
+            Field::CompletedAt => optional(todo.completed_at.map(|t| t.to_string())),
+        }
+    }
+
+    /// Set the field in `todo` from a cell. Relative due dates are resolved
+    /// against `today`.
+    pub fn set(self, todo: &mut Todo, cell: &str, today: Date) -> Result<(), Error> {
+        let cell = cell.trim();
+        let timestamp = |cell: &str| cell.parse::<Timestamp>().map(Some);
+        match self {
+            Field::Id => {}
+            Field::Description => todo.description = cell.to_string(),
+            Field::Status => todo.status = if cell.is_empty() { Status::Todo } else { cell.to_lowercase().parse()? },
+            Field::Priority => todo.priority = Some(cell).filter(|cell| !cell.is_empty()).map(str::parse).transpose()?,
+            Field::Due => todo.due = Some(cell).filter(|cell| !cell.is_empty()).map(|due| Date::parse(due, today)).transpose()?,
+            Field::Tags => {
+                todo.tags.clear();
+                for tag in cell.split([' ', ',']).map(|tag| tag.trim_start_matches('+')).filter(|tag| !tag.is_empty()) {
+                    if !todo.tags.iter().any(|t| t == tag) {
+                        todo.tags.push(tag.to_string());
+                    }
+                }
+            }
+            Field::Project => todo.project = Some(cell.trim_start_matches('@').to_string()).filter(|p| !p.is_empty()),
+            Field::CreatedAt if !cell.is_empty() => todo.created_at = timestamp(cell)?,
+            Field::UpdatedAt if !cell.is_empty() => todo.updated_at = timestamp(cell)?,
+            Field::CompletedAt if !cell.is_empty() => todo.completed_at = timestamp(cell)?,
+            Field::CreatedAt | Field::UpdatedAt | Field::CompletedAt => {}
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Accepts the column names in any case, with `-` or a space for `_`.
+impl FromStr for Field {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Field, Error> {
+        let name = normalize(s);
+        Field::ALL.into_iter().find(|field| field.as_str() == name).ok_or_else(|| {
+            let names: Vec<_> = Field::ALL.iter().map(|field| field.as_str()).collect();
+            Error::Validation(format!("unknown field `{}` (expected one of: {})", s, names.join(", ")))
+        })
+    }
+}
+
+/// What an import made of a file: the todos from the valid rows, a message
+/// for each row that isn't valid, and the header of every column that was
+/// ignored.
+#[derive(Debug, Default)]
+pub struct Rows {
+    /// A todo (with ID 0) for each valid row, in file order.
+    pub todos: Vec<Todo>,
+    /// `line N: ...` for each invalid row.
+    pub errors: Vec<String>,
+    /// Headers that match no field.
+    pub ignored: Vec<String>,
+}
+
+/// Write todos as a table with the given columns and a header row.
+/// `delimiter` is `,` for CSV, where cells are quoted as needed, or `\t` for
+/// TSV, where tabs in cells become spaces.
+pub fn format(todos: &[Todo], columns: &[Field], delimiter: char) -> String {
+    let cell = |value: String| {
+        if delimiter == '\t' {
+            value.replace(['\t', '\n', '\r'], " ")
+        } else if value.contains([delimiter, '"', '\n', '\r']) || value.trim() != value {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value
+        }
+    };
+    let separator = delimiter.to_string();
+    let header: Vec<String> = columns.iter().map(|field| field.as_str().to_string()).collect();
+    let mut out = header.join(&separator) + "\n";
+    for todo in todos {
+        let row: Vec<String> = columns.iter().map(|field| cell(field.value(todo))).collect();
+        out.push_str(&row.join(&separator));
+        out.push('\n');
+    }
+    out
+}
+
+/// Read a table with a header row. `mapping` maps header names (matched
+/// ignoring case) onto fields, on top of the headers named like a field.
+///
+/// Fails only if the file can't be read as a table or has no description
+/// column; problems with single rows are collected in [`Rows::errors`].
+pub fn parse(text: &str, delimiter: char, mapping: &[(String, Field)], today: Date) -> Result<Rows, Error> {
+    let mut records = records(text.strip_prefix('\u{feff}').unwrap_or(text), delimiter)?.into_iter();
+    let Some((_, header)) = records.next() else {
+        return Err(Error::Validation("the file is empty (expected a header row)".to_string()));
+    };
+
+    let mut rows = Rows::default();
+    let mut columns = Vec::new();
+    for name in &header {
+        let mapped = mapping.iter().find(|(from, _)| from.trim().eq_ignore_ascii_case(name.trim()));
+        match mapped.map(|(_, field)| Ok(*field)).unwrap_or_else(|| name.parse::<Field>()) {
+            Ok(field) => columns.push(Some(field)),
+            Err(_) => {
+                rows.ignored.push(name.clone());
+                columns.push(None);
+            }
+        }
+    }
+    if !columns.contains(&Some(Field::Description)) {
+        return Err(Error::Validation(format!(
+            "no column holds the description (the columns are: {}); use --map \"<column>=description\"",
+            header.join(", ")
+        )));
+    }
+
+    for (line, record) in records {
+        if record.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        let mut todo = Todo::new("");
+        let result = columns
+            .iter()
+            .zip(&record)
+            .filter_map(|(field, cell)| field.map(|field| (field, cell)))
+            .try_for_each(|(field, cell)| {
+                field.set(&mut todo, cell, today).map_err(|e| format!("{}: {}", field, e))
+            })
+            .and_then(|()| todo.validate().map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => {
+                if todo.status != Status::Done {
+                    todo.completed_at = None;
+                }
+                rows.todos.push(todo);
+            }
+            Err(message) => rows.errors.push(format!("line {}: {}", line, message)),
+        }
+    }
+    Ok(rows)
+}
+
+/// Split text into records of cells, each with the 1-based line it starts
+/// on. Handles quoted cells with doubled quotes and line breaks (TSV has no
+/// quoting).
+fn records(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<String>)>, Error> {
+    let quoting = delimiter != '\t';
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut cell = String::new();
+    let mut line = 1;
+    let mut start = 1;
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+        }
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if quoting && cell.is_empty() => quoted = true,
+            c if quoted => cell.push(c),
+            c if c == delimiter => record.push(std::mem::take(&mut cell)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut cell));
+                records.push((start, std::mem::take(&mut record)));
+                start = line;
+            }
+            c => cell.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::Validation(format!("line {}: unterminated quoted cell", start)));
+    }
+    if !cell.is_empty() || !record.is_empty() {
+        record.push(cell);
+        records.push((start, record));
+    }
+    Ok(records)
+}
+
+/// `Created At` -> `created_at`
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase().replace([' ', '-'], "_")
+}
@@ -0,0 +1,381 @@
+//! Calendar dates for due dates, including the natural-language forms
+//! accepted on the command line ("tomorrow", "next friday", "in 3 days"),
+//! and timestamps for recording when things happened.
+//!
+//! Dates are plain days in the proleptic Gregorian calendar with no time zone;
+//! "today" is taken from the local clock. Timestamps are instants, written
+//! in UTC.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::Error;
+
+const WEEKDAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+/// The years a date can have: the ones `YYYY-MM-DD` can write.
+const YEARS: RangeInclusive<i32> = 1..=9999;
+
+/// A calendar day, such as 2026-11-01. Orders chronologically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    /// The given day, or `None` if it doesn't exist (e.g. February 30th) or
+    /// its year is outside 1 to 9999.
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Date> {
+        let valid = YEARS.contains(&year)
+            && (1..=12).contains(&month)
+            && day >= 1
+            && day <= days_in_month(year, month);
+        valid.then_some(Date { year, month, day })
+    }
+
+    /// The year, e.g. 2026.
+    pub fn year(self) -> i32 {
+        self.year
+    }
+
+    /// The month, 1 to 12.
+    pub fn month(self) -> u32 {
+        self.month
+    }
+
+    /// The day of the month, 1 to 31.
+    pub fn day(self) -> u32 {
+        self.day
+    }
+
+    /// Today's date on the local clock.
+    pub fn today() -> Date {
+        let now = Timestamp::now().unix();
+        Date::from_days((now + local_utc_offset(now)).div_euclid(86_400))
+    }
+
+    /// Parse a date the way a person would type it, relative to `today`:
+    ///
+    /// - `2026-11-01` (ISO 8601)
+    /// - `today`, `tomorrow`, `yesterday`
+    /// - `friday` or `next friday`: the first Friday after today (also `fri`)
+    /// - `next week`, `next month`, `next year`
+    /// - `in 3 days`, `in 2 weeks`, `in 1 month`, `in a year`
+    ///
+    /// Dates that would fall outside the years 1 to 9999 are rejected.
+    pub fn parse(input: &str, today: Date) -> Result<Date, Error> {
+        let text = input.trim().to_lowercase();
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let in_range = |date: Option<Date>| {
+            date.ok_or_else(|| Error::Validation(format!("the date `{}` is out of range (years 1 to 9999)", input)))
+        };
+        let parsed = match words.as_slice() {
+            ["today"] => Some(today),
+            ["tomorrow"] => Some(in_range(today.add_days(1))?),
+            ["yesterday"] => Some(in_range(today.add_days(-1))?),
+            ["next", "week"] => Some(in_range(today.add_days(7))?),
+            ["next", "month"] => Some(in_range(today.add_months(1))?),
+            ["next", "year"] => Some(in_range(today.add_months(12))?),
+            ["next", day] | [day] if weekday_number(day).is_some() => {
+                let target = weekday_number(day).unwrap_or_default();
+                let ahead = (target + 7 - today.weekday()) % 7;
+                Some(in_range(today.add_days(if ahead == 0 { 7 } else { ahead as i64 }))?)
+            }
+            ["in", count, unit] => {
+                let count = match *count {
+                    "a" | "an" | "one" => Some(1),
+                    count => count.parse::<i64>().ok(),
+                };
+                let date = match (count, unit.trim_end_matches('s')) {
+                    (Some(n), "day") => Some(today.add_days(n)),
+                    (Some(n), "week") => Some(n.checked_mul(7).and_then(|days| today.add_days(days))),
+                    (Some(n), "month") => Some(today.add_months(n)),
+                    (Some(n), "year") => Some(n.checked_mul(12).and_then(|months| today.add_months(months))),
+                    _ => None,
+                };
+                date.map(in_range).transpose()?
+            }
+            // Report what's wrong with an ISO date rather than the generic hint
+            [word] if word.starts_with(|c: char| c.is_ascii_digit()) => return word.parse(),
+            _ => None,
+        };
+        parsed.ok_or_else(|| {
+            Error::Validation(format!(
+                "can't understand the date `{}` (try 2026-11-01, tomorrow, friday or \"in 3 days\")",
+                input
+            ))
+        })
+    }
+
+    /// The date `days` days later (or earlier, if negative), or `None` if
+    /// that is outside the years 1 to 9999.
+    pub fn add_days(self, days: i64) -> Option<Date> {
+        let first = Date { year: *YEARS.start(), month: 1, day: 1 };
+        let last = Date { year: *YEARS.end(), month: 12, day: 31 };
+        let days = self.to_days().checked_add(days)?;
+        (first.to_days()..=last.to_days()).contains(&days).then(|| Date::from_days(days))
+    }
+
+    /// The same day `months` months later, clamped to the end of shorter
+    /// months (January 31st + 1 month = February 28th or 29th), or `None`
+    /// if that is outside the years 1 to 9999.
+    pub fn add_months(self, months: i64) -> Option<Date> {
+        let index = (self.year as i64 * 12 + (self.month as i64 - 1)).checked_add(months)?;
+        let year = i32::try_from(index.div_euclid(12)).ok().filter(|year| YEARS.contains(year))?;
+        let month = index.rem_euclid(12) as u32 + 1;
+        let day = self.day.min(days_in_month(year, month));
+        Some(Date { year, month, day })
+    }
+
+    /// Day of the week, 0 for Monday through 6 for Sunday.
+    pub fn weekday(self) -> u32 {
+        // 1970-01-01 was a Thursday
+        (self.to_days() + 3).rem_euclid(7) as u32
+    }
+
+    /// Days since 1970-01-01 (Howard Hinnant's `days_from_civil`).
+    pub(crate) fn to_days(self) -> i64 {
+        let year = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// The date `days` days after 1970-01-01 (Howard Hinnant's `civil_from_days`).
+    pub(crate) fn from_days(days: i64) -> Date {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (year_of_era + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+        Date { year, month, day }
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// 0 for Monday through 6 for Sunday; accepts full names and 3-letter abbreviations.
+fn weekday_number(name: &str) -> Option<u32> {
+    WEEKDAYS
+        .iter()
+        .position(|day| *day == name || (name.len() == 3 && day.starts_with(name)))
+        .map(|i| i as u32)
+}
+
+/// Seconds east of UTC for the local time zone at the given Unix time.
+#[cfg(unix)]
+fn local_utc_offset(unix_time: i64) -> i64 {
+    use std::ffi::{c_char, c_int, c_long};
+
+    /// `struct tm` as laid out by glibc, musl and the BSDs (including macOS).
+    #[repr(C)]
+    struct Tm {
+        tm_sec: c_int,
+        tm_min: c_int,
+        tm_hour: c_int,
+        tm_mday: c_int,
+        tm_mon: c_int,
+        tm_year: c_int,
+        tm_wday: c_int,
+        tm_yday: c_int,
+        tm_isdst: c_int,
+        tm_gmtoff: c_long,
+        tm_zone: *const c_char,
+    }
+
+    extern "C" {
+        fn tzset();
+        fn localtime_r(time: *const i64, result: *mut Tm) -> *mut Tm;
+    }
+
+    // SAFETY: `Tm` matches the platform's `struct tm`, both pointers are
+    // valid for the duration of the call, and tzset has no preconditions.
+    unsafe {
+        let mut tm: Tm = std::mem::zeroed();
+        tzset();
+        if localtime_r(&unix_time, &mut tm).is_null() {
+            return 0;
+        }
+        tm.tm_gmtoff as i64
+    }
+}
+
+#[cfg(not(unix))]
+fn local_utc_offset(_unix_time: i64) -> i64 {
+    0
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Strict ISO 8601 `YYYY-MM-DD`. Use [`Date::parse`] for relative dates.
+impl FromStr for Date {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Date, Error> {
+        let invalid = || Error::Validation(format!("invalid date `{}` (expected YYYY-MM-DD)", s));
+        let mut parts = s.splitn(3, '-');
+        let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return Err(invalid());
+        }
+        let year = year.parse().map_err(|_| invalid())?;
+        let month = month.parse().map_err(|_| invalid())?;
+        let day = day.parse().map_err(|_| invalid())?;
+        Date::new(year, month, day).ok_or_else(|| Error::Validation(format!("{} is not a valid date", s)))
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Date, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// An instant, to the second. Written in UTC as RFC 3339, e.g.
+/// `2026-10-15T14:03:00Z`; reading also accepts fractional seconds and
+/// `+HH:MM` / `-HH:MM` offsets of up to 23:59.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    secs: i64,
+}
+
+impl Timestamp {
+    /// The current time.
+    pub fn now() -> Timestamp {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        Timestamp { secs }
+    }
+
+    /// The instant `secs` seconds after 1970-01-01T00:00:00Z.
+    pub fn from_unix(secs: i64) -> Timestamp {
+        Timestamp { secs }
+    }
+
+    /// Midnight UTC at the start of `date`, for formats that only record days.
+    pub fn start_of(date: Date) -> Timestamp {
+        Timestamp { secs: date.to_days() * 86_400 }
+    }
+
+    /// Seconds since 1970-01-01T00:00:00Z.
+    pub fn unix(self) -> i64 {
+        self.secs
+    }
+
+    /// The UTC date of the instant.
+    pub fn date(self) -> Date {
+        Date::from_days(self.secs.div_euclid(86_400))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.secs.rem_euclid(86_400);
+        write!(f, "{}T{:02}:{:02}:{:02}Z", self.date(), time / 3600, time / 60 % 60, time % 60)
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Timestamp, Error> {
+        let invalid = || Error::Validation(format!("invalid timestamp `{}` (expected e.g. 2026-10-15T14:03:00Z)", s));
+        let (date, time) = s.split_once(['T', 't', ' ']).ok_or_else(invalid)?;
+        let date: Date = date.parse().map_err(|_| invalid())?;
+
+        // HH:MM:SS, then optional fractional seconds, then Z or an offset
+        let field = |text: &str, range: std::ops::Range<usize>| {
+            text.get(range).filter(|f| f.bytes().all(|b| b.is_ascii_digit())).and_then(|f| f.parse::<i64>().ok())
+        };
+        let (Some(hour), Some(minute), Some(second)) = (field(time, 0..2), field(time, 3..5), field(time, 6..8)) else {
+            return Err(invalid());
+        };
+        if time.get(2..3) != Some(":") || time.get(5..6) != Some(":") || hour > 23 || minute > 59 || second > 60 {
+            return Err(invalid());
+        }
+        let mut rest = &time[8..];
+        if let Some(fraction) = rest.strip_prefix('.') {
+            rest = fraction.trim_start_matches(|c: char| c.is_ascii_digit());
+        }
+        let offset = match rest {
+            "Z" | "z" => 0,
+            _ => {
+                let sign = match rest.get(..1) {
+                    Some("+") => 1,
+                    Some("-") => -1,
+                    _ => return Err(invalid()),
+                };
+                // ±HH:MM, at most a day either way
+                let (Some(hours), Some(minutes)) = (field(rest, 1..3), field(rest, 4..6)) else {
+                    return Err(invalid());
+                };
+                if rest.len() != 6 || rest.get(3..4) != Some(":") || hours > 23 || minutes > 59 {
+                    return Err(invalid());
+                }
+                sign * (hours * 3600 + minutes * 60)
+            }
+        };
+        let secs = date.to_days()
+            .checked_mul(86_400)
+            .and_then(|secs| secs.checked_add(hour * 3600 + minute * 60 + second))
+            .and_then(|secs| secs.checked_sub(offset))
+            .ok_or_else(invalid)?;
+        // An offset can move the first or last day out of range
+        let timestamp = Timestamp { secs };
+        if !YEARS.contains(&timestamp.date().year) {
+            return Err(invalid());
+        }
+        Ok(timestamp)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
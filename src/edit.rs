@@ -0,0 +1,286 @@
+//! Rendering a todo as a small TOML document for `$EDITOR`, and reading the
+//! edited document back.
+//!
+//! Only the subset of TOML that the rendering uses is understood: one
+//! `key = value` per line, with basic (`"..."`) or literal (`'...'`) strings,
+//! booleans, integers and single-line arrays, plus `#` comments.
+
+use std::collections::BTreeMap;
+
+use crate::{Date, Error, Priority, Status, Todo};
+
+/// A value on the right-hand side of `key = value`.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Bool(bool),
+    Integer(i64),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Bool(_) => "true or false",
+            Value::Integer(_) => "an integer",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+/// Render the editable fields of `todo` as TOML, with a comment explaining
+/// what to do. The ID is shown in the comment only, since it can't change.
+pub fn to_toml(todo: &Todo) -> String {
+    let mut out = format!(
+        "# Editing task {}. Save and quit to apply your changes;\n\
+         # delete everything in this file to cancel.\n\n",
+        todo.id
+    );
+    out.push_str(&format!("description = {}\n", quote(&todo.description)));
+    out.push_str(&format!("status = {}  # {}\n", quote(todo.status.as_str()), status_names()));
+    let priority = todo.priority.map(|priority| priority.as_str()).unwrap_or_default();
+    out.push_str(&format!("priority = {}  # {}; \"\" for none\n", quote(priority), priority_names()));
+    let due = todo.due.map(|due| due.to_string()).unwrap_or_default();
+    out.push_str(&format!("due = {}  # YYYY-MM-DD, \"tomorrow\", \"next friday\"...; \"\" for none\n", quote(&due)));
+    let tags: Vec<_> = todo.tags.iter().map(|tag| quote(tag)).collect();
+    out.push_str(&format!("tags = [{}]\n", tags.join(", ")));
+    let project = todo.project.as_deref().unwrap_or_default();
+    out.push_str(&format!("project = {}  # \"\" for none\n", quote(project)));
+    out
+}
+
+/// Read an edited document back and apply it to a copy of `todo`.
+///
+/// Returns `Ok(None)` when the document holds no fields at all, which means
+/// the user cancelled. Every field must be present; unknown fields, values of
+/// the wrong type and an invalid todo are rejected with `Error::Validation`.
+pub fn from_toml(todo: &Todo, text: &str) -> Result<Option<Todo>, Error> {
+    let mut fields = parse(text)?;
+    if fields.is_empty() {
+        return Ok(None);
+    }
+
+    let mut edited = todo.clone();
+    edited.description = take_string(&mut fields, "description")?;
+    edited.status = take_string(&mut fields, "status")?.parse()?;
+    edited.priority = match take_string(&mut fields, "priority")?.trim() {
+        "" => None,
+        priority => Some(priority.parse()?),
+    };
+    edited.due = match take_string(&mut fields, "due")?.trim() {
+        "" => None,
+        due => Some(Date::parse(due, Date::today())?),
+    };
+    edited.tags = take_strings(&mut fields, "tags")?;
+    edited.project = match take_string(&mut fields, "project")?.trim() {
+        "" => None,
+        project => Some(project.to_string()),
+    };
+
+    if let Some(key) = fields.keys().next() {
+        return Err(Error::Validation(format!("unknown field `{}`", key)));
+    }
+    edited.validate()?;
+    Ok(Some(edited))
+}
+
+/// The allowed statuses, for the comment next to the `status` field.
+fn status_names() -> String {
+    Status::ALL.iter().map(|status| status.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// The allowed priorities, for the comment next to the `priority` field.
+fn priority_names() -> String {
+    Priority::ALL.iter().map(|priority| priority.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+fn take(fields: &mut BTreeMap<String, Value>, key: &str) -> Result<Value, Error> {
+    fields.remove(key).ok_or_else(|| Error::Validation(format!("missing field `{}`", key)))
+}
+
+fn take_string(fields: &mut BTreeMap<String, Value>, key: &str) -> Result<String, Error> {
+    match take(fields, key)? {
+        Value::String(s) => Ok(s),
+        other => Err(wrong_type(key, "a string", &other)),
+    }
+}
+
+fn take_strings(fields: &mut BTreeMap<String, Value>, key: &str) -> Result<Vec<String>, Error> {
+    match take(fields, key)? {
+        Value::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Value::String(s) => Ok(s),
+                other => Err(wrong_type(key, "an array of strings", &other)),
+            })
+            .collect(),
+        other => Err(wrong_type(key, "an array of strings", &other)),
+    }
+}
+
+fn wrong_type(key: &str, expected: &str, got: &Value) -> Error {
+    Error::Validation(format!("`{}` must be {}, not {}", key, expected, got.type_name()))
+}
+
+/// Quote a string as a TOML basic string.
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Parse the whole document into its fields.
+fn parse(text: &str) -> Result<BTreeMap<String, Value>, Error> {
+    let mut fields = BTreeMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let mut cursor = Cursor { rest: line.trim_start() };
+        if cursor.at_end() {
+            continue;
+        }
+        let at_line = |message: String| Error::Validation(format!("line {}: {}", number + 1, message));
+
+        let key = cursor.key().map_err(at_line)?;
+        cursor.skip_space();
+        if !cursor.eat('=') {
+            return Err(at_line(format!("expected `=` after `{}`", key)));
+        }
+        let value = cursor.value().map_err(at_line)?;
+        if !cursor.at_end() {
+            return Err(at_line(format!("unexpected text after the value of `{}`", key)));
+        }
+        if fields.insert(key.clone(), value).is_some() {
+            return Err(at_line(format!("`{}` is set twice", key)));
+        }
+    }
+    Ok(fields)
+}
+
+/// A position within one line of the document.
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl Cursor<'_> {
+    fn skip_space(&mut self) {
+        self.rest = self.rest.trim_start_matches([' ', '\t']);
+    }
+
+    /// True once only whitespace or a comment is left.
+    fn at_end(&mut self) -> bool {
+        self.skip_space();
+        self.rest.is_empty() || self.rest.starts_with('#')
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        let end = self.rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err("expected a field name".to_string());
+        }
+        let key = self.rest[..end].to_string();
+        self.rest = &self.rest[end..];
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_space();
+        if self.eat('"') {
+            return self.basic_string().map(Value::String);
+        }
+        if self.eat('\'') {
+            let end = self.rest.find('\'').ok_or("unterminated string")?;
+            let s = self.rest[..end].to_string();
+            self.rest = &self.rest[end + 1..];
+            return Ok(Value::String(s));
+        }
+        if self.eat('[') {
+            let mut items = Vec::new();
+            loop {
+                self.skip_space();
+                if self.eat(']') {
+                    return Ok(Value::Array(items));
+                }
+                items.push(self.value()?);
+                self.skip_space();
+                if !self.eat(',') {
+                    self.skip_space();
+                    return if self.eat(']') { Ok(Value::Array(items)) } else { Err("expected `,` or `]`".to_string()) };
+                }
+            }
+        }
+
+        let end = self.rest
+            .find(|c: char| c.is_whitespace() || c == ',' || c == ']' || c == '#')
+            .unwrap_or(self.rest.len());
+        let word = &self.rest[..end];
+        self.rest = &self.rest[end..];
+        match word {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => word
+                .replace('_', "")
+                .parse()
+                .map(Value::Integer)
+                .map_err(|_| format!("invalid value `{}` (strings need quotes)", word)),
+        }
+    }
+
+    /// The rest of a `"..."` string, after the opening quote.
+    fn basic_string(&mut self) -> Result<String, String> {
+        let mut out = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(out);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some(kind @ ('u' | 'U')) => {
+                        let len = if kind == 'u' { 4 } else { 8 };
+                        let hex: String = chars.by_ref().take(len).map(|(_, c)| c).collect();
+                        // from_str_radix alone would also take a sign
+                        let c = Some(&hex)
+                            .filter(|hex| hex.len() == len && hex.chars().all(|c| c.is_ascii_hexdigit()))
+                            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape \\{}{}", kind, hex))?;
+                        out.push(c);
+                    }
+                    Some(other) => return Err(format!("invalid escape \\{}", other)),
+                    None => break,
+                },
+                c => out.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+}
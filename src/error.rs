@@ -0,0 +1,94 @@
+//! The error type shared by the whole crate, and the exit codes it maps to.
+
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while working with a todo list.
+///
+/// Each variant maps to its own process exit code (see [`Error::exit_code`]),
+/// so scripts can tell "no such task" apart from "the file is locked":
+///
+/// | Code | Variant | Meaning |
+/// |------|---------|---------|
+/// | 0 | - | success |
+/// | 2 | - | invalid command-line usage (reported by clap) |
+/// | 3 | `NotFound` | the task doesn't exist |
+/// | 4 | `Validation` | a value was rejected, or the configuration is invalid |
+/// | 5 | `Parse` | the todo or config file can't be understood |
+/// | 6 | `Lock` | another invocation held the lock for too long |
+/// | 7 | `Io` | reading or writing the data failed |
+#[derive(Debug)]
+pub enum Error {
+    /// The referenced task doesn't exist. Holds the reference as the user
+    /// gave it, e.g. `5` or `at position 3`.
+    NotFound(String),
+    /// A file system (or database) operation failed.
+    Io(io::Error),
+    /// A file couldn't be parsed, or was written by a newer version.
+    Parse(String),
+    /// The todo list is locked by another process.
+    Lock(String),
+    /// Input or configuration was rejected.
+    Validation(String),
+}
+
+/// Shorthand for results that fail with [`Error`].
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// The process exit code documented for this kind of error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NotFound(_) => 3,
+            Error::Validation(_) => 4,
+            Error::Parse(_) => 5,
+            Error::Lock(_) => 6,
+            Error::Io(_) => 7,
+        }
+    }
+
+    /// A short, stable name for the kind of error, e.g. `not-found`; used
+    /// in JSON output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::NotFound(_) => "not-found",
+            Error::Validation(_) => "validation",
+            Error::Parse(_) => "parse",
+            Error::Lock(_) => "lock",
+            Error::Io(_) => "io",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(task) => write!(f, "Task {} doesn't exist", task),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(message) | Error::Lock(message) | Error::Validation(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Parse(e.to_string())
+    }
+}
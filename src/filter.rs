@@ -0,0 +1,338 @@
+//! A small query language for picking todos, shared by `list`, the commands
+//! that change tasks, and `export`.
+//!
+//! ```text
+//! status:open and (tag:work or priority:H) and due:<2026-11-01 and "invoice"
+//! ```
+//!
+//! A filter is a boolean expression of terms combined with `and`, `or`, `not`
+//! and parentheses. Terms written next to each other are joined with `and`,
+//! which binds tighter than `or`. The terms are:
+//!
+//! | Term | Matches todos... |
+//! |------|------------------|
+//! | `status:open`, `status:closed` | that still need work / are done or cancelled |
+//! | `status:<name>` | with that status, e.g. `status:in-progress` |
+//! | `tag:<name>` | carrying the tag |
+//! | `project:<name>`, `project:none` | in the project / in no project |
+//! | `priority:H`, `priority:none` | with that priority (`H`, `M`, `L`) / none |
+//! | `due:<date>`, `due:<op><date>` | due on, or before/after, a date; `op` is `<`, `<=`, `>`, `>=` or `=` |
+//! | `due:any`, `due:none`, `due:overdue` | with a due date / without one / open and past it |
+//! | `id:<n>` | with that ID |
+//! | `word`, `"some words"` | whose description contains the text (ignoring case) |
+//!
+//! Dates accept everything [`Date::parse`] does; quote phrases with spaces,
+//! e.g. `due:<"next friday"`.
+
+use crate::{Date, Error, Priority, Status, Todo};
+
+/// A parsed filter expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// A single condition.
+    Term(Term),
+    /// Matches when the inner filter doesn't.
+    Not(Box<Filter>),
+    /// Matches when both sides do.
+    And(Box<Filter>, Box<Filter>),
+    /// Matches when either side does.
+    Or(Box<Filter>, Box<Filter>),
+}
+
+/// A single condition on a todo.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// `status:open` or `status:closed`: whether the task still needs work.
+    Open(bool),
+    /// `status:<name>`: exactly this status.
+    Status(Status),
+    /// `tag:<name>`: carries this tag.
+    Tag(String),
+    /// `project:<name>`, or `project:none` for `None`.
+    Project(Option<String>),
+    /// `priority:<H|M|L>`, or `priority:none` for `None`.
+    Priority(Option<Priority>),
+    /// `due:<op><date>`: has a due date that compares to the date this way.
+    Due(Comparison, Date),
+    /// `due:any` (`true`) or `due:none` (`false`): has a due date at all.
+    HasDue(bool),
+    /// `due:overdue`: open and due before the given day (today, when parsed).
+    Overdue(Date),
+    /// `id:<n>`: has this ID.
+    Id(u64),
+    /// Bare or quoted text: the description contains it, ignoring case.
+    /// Stored in lower case.
+    Text(String),
+}
+
+/// How a due date is compared in `due:` terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// `<`: strictly before.
+    Before,
+    /// `<=`: on or before.
+    OnOrBefore,
+    /// `=` (or no operator): on the day.
+    On,
+    /// `>=`: on or after.
+    OnOrAfter,
+    /// `>`: strictly after.
+    After,
+}
+
+impl Filter {
+    /// Parse a filter expression. Relative dates such as `due:<tomorrow` are
+    /// resolved against `today`.
+    pub fn parse(input: &str, today: Date) -> Result<Filter, Error> {
+        let tokens = lex(input).map_err(|message| invalid(input, message))?;
+        let mut parser = Parser { tokens, position: 0, today };
+        let filter = parser.or().map_err(|message| invalid(input, message))?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(filter),
+            Some(Token::RightParen) => Err(invalid(input, "unmatched `)`".to_string())),
+            Some(token) => Err(invalid(input, format!("unexpected {}", token.describe()))),
+        }
+    }
+
+    /// True if `todo` satisfies the filter.
+    pub fn matches(&self, todo: &Todo) -> bool {
+        match self {
+            Filter::Term(term) => term.matches(todo),
+            Filter::Not(inner) => !inner.matches(todo),
+            Filter::And(left, right) => left.matches(todo) && right.matches(todo),
+            Filter::Or(left, right) => left.matches(todo) || right.matches(todo),
+        }
+    }
+}
+
+impl Term {
+    /// True if `todo` satisfies the condition.
+    pub fn matches(&self, todo: &Todo) -> bool {
+        match self {
+            Term::Open(open) => todo.status.is_open() == *open,
+            Term::Status(status) => todo.status == *status,
+            Term::Tag(tag) => todo.tags.contains(tag),
+            Term::Project(project) => todo.project == *project,
+            Term::Priority(priority) => todo.priority == *priority,
+            Term::Due(comparison, date) => todo.due.is_some_and(|due| comparison.holds(due, *date)),
+            Term::HasDue(has_due) => todo.due.is_some() == *has_due,
+            Term::Overdue(today) => todo.is_overdue(*today),
+            Term::Id(id) => todo.id == *id,
+            Term::Text(text) => todo.description.to_lowercase().contains(text),
+        }
+    }
+}
+
+impl Comparison {
+    fn holds(self, left: Date, right: Date) -> bool {
+        match self {
+            Comparison::Before => left < right,
+            Comparison::OnOrBefore => left <= right,
+            Comparison::On => left == right,
+            Comparison::OnOrAfter => left >= right,
+            Comparison::After => left > right,
+        }
+    }
+}
+
+fn invalid(input: &str, message: String) -> Error {
+    Error::Validation(format!("invalid filter `{}`: {}", input, message))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    /// An unquoted word, possibly `key:value` (quotes inside are resolved).
+    Word(String),
+    /// A word that started with a quote, which is always description text.
+    Quoted(String),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::LeftParen => "`(`".to_string(),
+            Token::RightParen => "`)`".to_string(),
+            Token::And => "`and`".to_string(),
+            Token::Or => "`or`".to_string(),
+            Token::Not => "`not`".to_string(),
+            Token::Word(word) | Token::Quoted(word) => format!("`{}`", word),
+        }
+    }
+}
+
+/// Split the input into tokens. A word runs until whitespace or a
+/// parenthesis, and may contain quoted parts with spaces (`due:<"next friday"`).
+fn lex(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LeftParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RightParen);
+            }
+            _ => {
+                let quoted = c == '"';
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    if c != '"' {
+                        word.push(c);
+                        continue;
+                    }
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => word.extend(chars.next()),
+                            Some(c) => word.push(c),
+                            None => return Err("unterminated quote".to_string()),
+                        }
+                    }
+                }
+                tokens.push(match word.to_lowercase().as_str() {
+                    _ if quoted => Token::Quoted(word),
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent parser over the tokens:
+///
+/// ```text
+/// or      = and ("or" and)*
+/// and     = unary (["and"] unary)*
+/// unary   = "not" unary | primary
+/// primary = "(" or ")" | term
+/// ```
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    today: Date,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Filter, String> {
+        let mut filter = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, String> {
+        let mut filter = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                // Juxtaposed terms are an implicit `and`
+                Some(Token::LeftParen | Token::Not | Token::Word(_) | Token::Quoted(_)) => {}
+                _ => return Ok(filter),
+            }
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Filter, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Filter, String> {
+        match self.next() {
+            Some(Token::LeftParen) => {
+                let filter = self.or()?;
+                match self.next() {
+                    Some(Token::RightParen) => Ok(filter),
+                    _ => Err("missing `)`".to_string()),
+                }
+            }
+            Some(Token::Quoted(text)) => Ok(Filter::Term(Term::Text(text.to_lowercase()))),
+            Some(Token::Word(word)) => self.term(&word).map(Filter::Term),
+            Some(token) => Err(format!("expected a term, found {}", token.describe())),
+            None => Err("expected a term, found the end of the filter".to_string()),
+        }
+    }
+
+    fn term(&self, word: &str) -> Result<Term, String> {
+        let Some((key, value)) = word.split_once(':') else {
+            return Ok(Term::Text(word.to_lowercase()));
+        };
+        let none = value.eq_ignore_ascii_case("none");
+        match key.to_lowercase().as_str() {
+            "status" => match value.to_lowercase().as_str() {
+                "open" => Ok(Term::Open(true)),
+                "closed" => Ok(Term::Open(false)),
+                name => name.parse().map(Term::Status).map_err(|e| e.to_string()),
+            },
+            "tag" => Ok(Term::Tag(value.trim_start_matches('+').to_string())),
+            "project" if none => Ok(Term::Project(None)),
+            "project" => Ok(Term::Project(Some(value.trim_start_matches('@').to_string()))),
+            "priority" if none => Ok(Term::Priority(None)),
+            "priority" => value.parse().map(|p| Term::Priority(Some(p))).map_err(|e: Error| e.to_string()),
+            "due" => self.due(value),
+            "id" => value.parse().map(Term::Id).map_err(|_| format!("invalid ID `{}`", value)),
+            _ => Err(format!(
+                "unknown field `{}` (expected status, tag, project, priority, due or id; quote text that contains `:`)",
+                key
+            )),
+        }
+    }
+
+    fn due(&self, value: &str) -> Result<Term, String> {
+        match value.to_lowercase().as_str() {
+            "any" => return Ok(Term::HasDue(true)),
+            "none" => return Ok(Term::HasDue(false)),
+            "overdue" => return Ok(Term::Overdue(self.today)),
+            _ => {}
+        }
+        let (comparison, date) = [
+            ("<=", Comparison::OnOrBefore),
+            (">=", Comparison::OnOrAfter),
+            ("<", Comparison::Before),
+            (">", Comparison::After),
+            ("=", Comparison::On),
+        ]
+        .into_iter()
+        .find_map(|(op, comparison)| value.strip_prefix(op).map(|date| (comparison, date)))
+        .unwrap_or((Comparison::On, value));
+        let date = Date::parse(date, self.today).map_err(|e| e.to_string())?;
+        Ok(Term::Due(comparison, date))
+    }
+}
@@ -0,0 +1,315 @@
+//! Reading and writing iCalendar ([RFC 5545]) files, with one `VTODO`
+//! component per task, so tasks show up in calendar and mail clients.
+//!
+//! | `VTODO` property | `Todo` |
+//! |------------------|--------|
+//! | `UID` | `<created>-<id>@cli-todo-rust`, or the `uid` in `extra` for imported tasks |
+//! | `SUMMARY` | `description` |
+//! | `STATUS` | `NEEDS-ACTION` todo or blocked, `IN-PROCESS`, `COMPLETED`, `CANCELLED` |
+//! | `PRIORITY` | 1 high, 5 medium, 9 low (reading 1-4, 5, 6-9; 0 is none) |
+//! | `DUE` | `due` (as a date) |
+//! | `CATEGORIES` | `tags` |
+//! | `CREATED`, `LAST-MODIFIED`, `COMPLETED` | `created_at`, `updated_at`, `completed_at` |
+//! | `X-CLI-TODO-RUST-PROJECT` | `project` |
+//! | `X-CLI-TODO-RUST-STATUS` | `blocked`, which iCalendar has no status for |
+//!
+//! [RFC 5545]: https://www.rfc-editor.org/rfc/rfc5545
+
+use serde_json::Value;
+
+use crate::{Date, Error, Priority, Status, Timestamp, Todo};
+
+/// Content lines longer than this many bytes are folded onto the next line.
+const MAX_LINE: usize = 75;
+const PROJECT: &str = "X-CLI-TODO-RUST-PROJECT";
+const STATUS: &str = "X-CLI-TODO-RUST-STATUS";
+
+/// The `UID` a todo is exported with. Tasks imported from another calendar
+/// keep the UID they came with, so importing them again finds them. Others
+/// get one made from their creation time (as a Unix time) and ID: the ID
+/// alone is only unique within one list, and importing another list's
+/// export must not update the unrelated task that has the same ID here.
+pub fn uid(todo: &Todo) -> String {
+    match todo.extra.get("uid").and_then(Value::as_str) {
+        Some(uid) => uid.to_string(),
+        None => format!("{}-{}@cli-todo-rust", todo.created_at.map_or(0, Timestamp::unix), todo.id),
+    }
+}
+
+/// Write todos as an iCalendar file with one `VTODO` each.
+pub fn format(todos: &[Todo]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//cli-todo-rust//EN".to_string(),
+    ];
+    for todo in todos {
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:{}", escape(&uid(todo))));
+        lines.push(format!("DTSTAMP:{}", date_time(todo.updated_at.unwrap_or_else(Timestamp::now))));
+        lines.extend(todo.created_at.map(|t| format!("CREATED:{}", date_time(t))));
+        lines.extend(todo.updated_at.map(|t| format!("LAST-MODIFIED:{}", date_time(t))));
+        lines.push(format!("SUMMARY:{}", escape(&todo.description)));
+        let status = match todo.status {
+            Status::Todo | Status::Blocked => "NEEDS-ACTION",
+            Status::InProgress => "IN-PROCESS",
+            Status::Done => "COMPLETED",
+            Status::Cancelled => "CANCELLED",
+        };
+        lines.push(format!("STATUS:{}", status));
+        if todo.status == Status::Blocked {
+            lines.push(format!("{}:{}", STATUS, todo.status));
+        }
+        lines.extend(todo.priority.map(|priority| {
+            let level = match priority {
+                Priority::High => 1,
+                Priority::Medium => 5,
+                Priority::Low => 9,
+            };
+            format!("PRIORITY:{}", level)
+        }));
+        lines.extend(todo.due.map(|due| format!("DUE;VALUE=DATE:{}", compact_date(due))));
+        lines.extend(todo.completed_at.map(|t| format!("COMPLETED:{}", date_time(t))));
+        if !todo.tags.is_empty() {
+            let tags: Vec<String> = todo.tags.iter().map(|tag| escape(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        lines.extend(todo.project.as_ref().map(|project| format!("{}:{}", PROJECT, escape(project))));
+        lines.push("END:VTODO".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+/// Read every `VTODO` in an iCalendar file. Each todo has ID 0 and its
+/// `UID` in `extra`, for matching it against the tasks already stored.
+/// Other components (events, alarms...) and unknown properties are ignored.
+pub fn parse(text: &str) -> Result<Vec<Todo>, Error> {
+    let mut todos = Vec::new();
+    let mut current: Option<Todo> = None;
+    // Components nested in the current VTODO (such as VALARM)
+    let mut nested = 0;
+    // STATUS can come after the properties that refine it
+    let mut status = None;
+    let mut blocked = false;
+
+    for (number, line) in unfold(text).iter().filter(|(_, line)| !line.is_empty()) {
+        let invalid = |message: String| Error::Validation(format!("line {}: {}", number, message));
+        let (name, value) = split_line(line).ok_or_else(|| invalid(format!("invalid content line `{}`", line)))?;
+        let Some(todo) = current.as_mut() else {
+            if name == "BEGIN" && value.eq_ignore_ascii_case("VTODO") {
+                current = Some(Todo::new(""));
+                (status, blocked) = (None, false);
+            }
+            continue;
+        };
+        match name.as_str() {
+            "BEGIN" => nested += 1,
+            "END" if nested > 0 => nested -= 1,
+            _ if nested > 0 => {}
+            "END" => {
+                let mut todo = current.take().unwrap_or_else(|| Todo::new(""));
+                todo.status = match status {
+                    Some(Status::Todo) if blocked => Status::Blocked,
+                    Some(status) => status,
+                    None if todo.completed_at.is_some() => Status::Done,
+                    None => Status::Todo,
+                };
+                if todo.status != Status::Done {
+                    todo.completed_at = None;
+                }
+                let uid = todo.extra.get("uid").and_then(Value::as_str).unwrap_or("without a UID").to_string();
+                todo.validate().map_err(|e| invalid(format!("task {}: {}", uid, e)))?;
+                todos.push(todo);
+            }
+            "UID" => {
+                todo.extra.insert("uid".to_string(), Value::from(unescape(value)));
+            }
+            "SUMMARY" => todo.description = unescape(value).split_whitespace().collect::<Vec<_>>().join(" "),
+            "STATUS" => {
+                status = Some(match value.to_uppercase().as_str() {
+                    "IN-PROCESS" => Status::InProgress,
+                    "COMPLETED" => Status::Done,
+                    "CANCELLED" => Status::Cancelled,
+                    _ => Status::Todo,
+                })
+            }
+            STATUS => blocked = value.eq_ignore_ascii_case("blocked"),
+            "PRIORITY" => {
+                todo.priority = match value.trim().parse::<u32>() {
+                    Ok(0) => None,
+                    Ok(1..=4) => Some(Priority::High),
+                    Ok(5) => Some(Priority::Medium),
+                    Ok(6..=9) => Some(Priority::Low),
+                    _ => return Err(invalid(format!("invalid PRIORITY `{}` (expected 0 to 9)", value))),
+                }
+            }
+            "DUE" => todo.due = Some(parse_date(value).ok_or_else(|| invalid(format!("invalid DUE `{}`", value)))?),
+            "CREATED" | "LAST-MODIFIED" | "COMPLETED" => {
+                let time = Some(parse_date_time(value).ok_or_else(|| invalid(format!("invalid {} `{}`", name, value)))?);
+                match name.as_str() {
+                    "CREATED" => todo.created_at = time,
+                    "LAST-MODIFIED" => todo.updated_at = time,
+                    _ => todo.completed_at = time,
+                }
+            }
+            "CATEGORIES" => {
+                for category in split_list(value) {
+                    // Tags are single words
+                    let tag = category.split_whitespace().collect::<Vec<_>>().join("-");
+                    if !tag.is_empty() && !todo.tags.contains(&tag) {
+                        todo.tags.push(tag);
+                    }
+                }
+            }
+            PROJECT => todo.project = Some(unescape(value)).filter(|project| !project.is_empty()),
+            _ => {}
+        }
+    }
+    if current.is_some() {
+        return Err(Error::Validation("the file ends inside a VTODO (missing END:VTODO)".to_string()));
+    }
+    Ok(todos)
+}
+
+/// The stored todo `existing` updated with what an imported copy of it
+/// says. Its ID, creation time, parent task and `extra` fields are kept.
+pub fn merge(existing: &Todo, imported: &Todo) -> Todo {
+    Todo {
+        id: existing.id,
+        created_at: existing.created_at.or(imported.created_at),
+        updated_at: existing.updated_at,
+        completed_at: imported.completed_at.or(existing.completed_at).filter(|_| imported.status == Status::Done),
+        parent: existing.parent,
+        extra: existing.extra.clone(),
+        ..imported.clone()
+    }
+}
+
+/// Split a content line into its upper-cased name and its value, which
+/// starts at the first `:` outside a quoted parameter. Parameters such as
+/// `VALUE=DATE` are dropped: the values themselves show whether they are
+/// dates or date-times.
+fn split_line(line: &str) -> Option<(String, &str)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let name = head.split(';').next().unwrap_or_default();
+    (!name.is_empty()).then(|| (name.to_uppercase(), value))
+}
+
+/// Join folded lines back together: a line break followed by a space or tab
+/// continues the previous line. Each line comes with the 1-based number of
+/// the line it starts on.
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (i, line) in text.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)).enumerate() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((_, last))) => last.push_str(rest),
+            _ => lines.push((i + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// End a content line with CRLF, folding it so no line is longer than 75
+/// bytes. Folds never split a UTF-8 character.
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// Escape a TEXT value: backslashes, semicolons, commas and line breaks.
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Split a list value at the commas that aren't escaped, unescaping each item.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => item.extend(std::iter::once(c).chain(chars.next())),
+            ',' => items.push(unescape(&std::mem::take(&mut item))),
+            c => item.push(c),
+        }
+    }
+    items.push(unescape(&item));
+    items
+}
+
+/// `2026-11-01` -> `20261101`
+fn compact_date(date: Date) -> String {
+    format!("{:04}{:02}{:02}", date.year(), date.month(), date.day())
+}
+
+/// A UTC DATE-TIME, e.g. `20261015T091244Z`. Taskwarrior writes its dates
+/// the same way.
+pub(crate) fn date_time(time: Timestamp) -> String {
+    let secs = time.unix().rem_euclid(86_400);
+    format!("{}T{:02}{:02}{:02}Z", compact_date(time.date()), secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// The date of a DATE (`20261101`) or DATE-TIME (`20261101T090000Z`) value.
+fn parse_date(value: &str) -> Option<Date> {
+    let digits = value.get(..8).filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))?;
+    Date::new(digits[..4].parse().ok()?, digits[4..6].parse().ok()?, digits[6..].parse().ok()?)
+}
+
+/// A DATE-TIME value; one without a `Z` (local "floating" time) is read as UTC.
+pub(crate) fn parse_date_time(value: &str) -> Option<Timestamp> {
+    let date = parse_date(value)?;
+    let time = value.get(8..)?.strip_prefix('T')?.trim_end_matches('Z');
+    if time.len() != 6 || !time.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    format!("{}T{}:{}:{}Z", date, &time[..2], &time[2..4], &time[4..]).parse().ok()
+}
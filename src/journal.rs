@@ -0,0 +1,183 @@
+//! The operation journal behind `undo`, `redo` and `history`.
+//!
+//! Every save that changes the list appends an [`Entry`] recording each
+//! changed todo before and after. The journal lives next to the data file
+//! (`todos.json.journal`) and has a cursor: undo steps it back and restores
+//! the "before" states, redo steps it forward again. Saving a new change
+//! after an undo discards the entries that could have been redone.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::store::{self, SCHEMA_VERSION};
+use crate::{Error, Status, Timestamp, Todo};
+
+/// How many entries are kept; older ones are forgotten.
+const MAX_ENTRIES: usize = 100;
+
+/// One saved change to the list: everything a single command did.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// When the change was saved.
+    pub time: Timestamp,
+    /// Every todo the change touched.
+    pub changes: Vec<Change>,
+}
+
+/// How one todo changed: `before` is `None` if it was added, `after` is
+/// `None` if it was removed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    /// The ID of the todo.
+    pub id: u64,
+    /// The todo before the change.
+    pub before: Option<Todo>,
+    /// The todo after the change.
+    pub after: Option<Todo>,
+}
+
+impl Change {
+    /// The command that makes this change, e.g. `add` or `complete`.
+    pub fn action(&self) -> &'static str {
+        match (&self.before, &self.after) {
+            (None, _) => "add",
+            (_, None) => "remove",
+            (Some(before), Some(after)) if before.status != after.status && before.description == after.description => {
+                match after.status {
+                    Status::Todo => "reopen",
+                    Status::InProgress => "start",
+                    Status::Blocked => "block",
+                    Status::Done => "complete",
+                    Status::Cancelled => "cancel",
+                }
+            }
+            _ => "edit",
+        }
+    }
+}
+
+impl Entry {
+    /// A one-line description, e.g. `complete 3 (Buy milk)` or
+    /// `remove 3 tasks (1, 4, 5)`.
+    pub fn summary(&self) -> String {
+        let action = self.changes.first().map_or("change", Change::action);
+        let action = if self.changes.iter().all(|change| change.action() == action) { action } else { "change" };
+        match self.changes.as_slice() {
+            [change] => {
+                let todo = change.after.as_ref().or(change.before.as_ref());
+                format!("{} {} ({})", action, change.id, todo.map_or("", |todo| todo.description.as_str()))
+            }
+            changes => {
+                let ids: Vec<String> = changes.iter().map(|change| change.id.to_string()).collect();
+                format!("{} {} tasks ({})", action, changes.len(), ids.join(", "))
+            }
+        }
+    }
+}
+
+/// The journal file: its entries, and how many of them are currently applied.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    /// Schema version of the todos stored in the entries.
+    version: u64,
+    /// Entries before the cursor are applied and can be undone; entries
+    /// from the cursor on were undone and can be redone.
+    cursor: usize,
+    entries: Vec<Entry>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Journal {
+    /// The journal kept for the todo file at `data_path`.
+    pub fn path_for(data_path: &Path) -> PathBuf {
+        store::sibling_path(data_path, "journal")
+    }
+
+    /// Load the journal at `path`, or start an empty one if it doesn't exist.
+    /// Todos recorded by an older version are upgraded like the data file.
+    pub fn load(path: &Path) -> Result<Journal, Error> {
+        if !path.exists() {
+            return Ok(Journal { version: SCHEMA_VERSION, path: path.to_path_buf(), ..Journal::default() });
+        }
+        let context = |message: String| Error::Parse(format!("cannot load the undo journal {}: {}", path.display(), message));
+        let mut doc: Value = serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| context(e.to_string()))?;
+        let version = doc.get("version").and_then(Value::as_u64).unwrap_or(SCHEMA_VERSION);
+        if version > SCHEMA_VERSION {
+            return Err(context(format!("it was written by a newer version (schema {})", version)));
+        }
+        if version < SCHEMA_VERSION {
+            upgrade(&mut doc, version).map_err(|e| context(e.to_string()))?;
+        }
+        let mut journal: Journal = serde_json::from_value(doc).map_err(|e| context(e.to_string()))?;
+        journal.path = path.to_path_buf();
+        journal.cursor = journal.cursor.min(journal.entries.len());
+        Ok(journal)
+    }
+
+    /// Write the journal back to its file.
+    pub fn save(&self) -> Result<(), Error> {
+        store::replace_file(&self.path, &serde_json::to_string_pretty(self)?, false)
+    }
+
+    /// Every entry, oldest first, with whether it is currently applied.
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = (&Entry, bool)> {
+        self.entries.iter().enumerate().map(|(i, entry)| (entry, i < self.cursor))
+    }
+
+    /// Record a new change, dropping anything that could have been redone.
+    pub fn record(&mut self, entry: Entry) {
+        self.entries.truncate(self.cursor);
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+        }
+        self.version = SCHEMA_VERSION;
+        self.cursor = self.entries.len();
+    }
+
+    /// The entry `undo` would revert, if any.
+    pub fn undoable(&self) -> Option<&Entry> {
+        self.cursor.checked_sub(1).map(|i| &self.entries[i])
+    }
+
+    /// The entry `redo` would re-apply, if any.
+    pub fn redoable(&self) -> Option<&Entry> {
+        self.entries.get(self.cursor)
+    }
+
+    /// Move the cursor back over the entry returned by `undoable`.
+    pub fn step_back(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    /// Move the cursor forward over the entry returned by `redoable`.
+    pub fn step_forward(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.entries.len());
+    }
+}
+
+/// Run the todos recorded in a journal of schema `version` through the
+/// data file's migrations.
+fn upgrade(doc: &mut Value, version: u64) -> Result<(), Error> {
+    let changes = doc
+        .get_mut("entries")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get_mut("changes").and_then(Value::as_array_mut))
+        .flatten();
+    for change in changes {
+        for side in ["before", "after"] {
+            let Some(todo) = change.get_mut(side).filter(|todo| !todo.is_null()) else {
+                continue;
+            };
+            let migrated = store::migrate_todos(version, vec![todo.take()])?;
+            *todo = serde_json::to_value(&migrated[0])?;
+        }
+    }
+    doc["version"] = SCHEMA_VERSION.into();
+    Ok(())
+}
@@ -0,0 +1,42 @@
+//! A small todo list library, and the engine behind the `cli-todo-rust` binary.
+//!
+//! - [`Todo`] is the data model; [`Date`] holds due dates and understands
+//!   phrases like "tomorrow" and "next friday", and [`Timestamp`] records
+//!   when things happened.
+//! - [`TodoList`] offers the operations (add, complete, remove, query) on top
+//!   of any storage backend; [`SortOrder`] decides how lists are presented.
+//! - [`store`] holds the `TodoStore` trait and its JSON, SQLite and
+//!   event-log backends.
+//! - [`Error`] is the one error type every fallible call returns; each kind
+//!   maps to a documented process exit code.
+//! - [`journal`] records every saved change, for undo and redo.
+//! - [`filter`] parses filter expressions like `status:open and tag:work`.
+//! - [`todotxt`], [`ical`], [`csv`], [`markdown`] and [`taskwarrior`] read
+//!   and write the todo.txt, iCalendar, CSV/TSV, Markdown and Taskwarrior
+//!   formats for import and export; [`markdown`] also keeps a task list
+//!   file in sync.
+//! - [`edit`] renders a todo as TOML for editing in `$EDITOR` and reads it back.
+//! - [`config`] finds the todo file the same way the CLI does, so other tools
+//!   see the same list.
+
+#![warn(missing_docs)]
+
+pub mod config;
+pub mod csv;
+mod date;
+pub mod edit;
+mod error;
+pub mod filter;
+pub mod ical;
+pub mod journal;
+mod list;
+pub mod markdown;
+mod model;
+pub mod store;
+pub mod taskwarrior;
+pub mod todotxt;
+
+pub use date::{Date, Timestamp};
+pub use error::{Error, Result};
+pub use list::{NumberList, Selection, SortOrder, TodoList};
+pub use model::{Priority, Status, Todo};
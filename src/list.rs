@@ -0,0 +1,463 @@
+//! `TodoList`: the operations the CLI offers, on top of any `TodoStore`.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::filter::Filter;
+use crate::store::{self, TodoStore};
+use crate::journal::{Change, Entry, Journal};
+use crate::{Date, Error, Status, Timestamp, Todo};
+
+/// How to order todos for display.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Open tasks first, then by priority (high to low, unset last), then by
+    /// due date (earliest first, unset last), then by ID.
+    #[default]
+    Urgency,
+    /// By priority (high to low, unset last), then by ID.
+    Priority,
+    /// By due date (earliest first, unset last), then by priority, then by ID.
+    Due,
+    /// By ID, which is the order the tasks were added in.
+    Id,
+}
+
+impl SortOrder {
+    /// Every sort order, with the default first.
+    pub const ALL: [SortOrder; 4] = [SortOrder::Urgency, SortOrder::Priority, SortOrder::Due, SortOrder::Id];
+
+    /// The name used on the command line, e.g. `priority`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Urgency => "urgency",
+            SortOrder::Priority => "priority",
+            SortOrder::Due => "due",
+            SortOrder::Id => "id",
+        }
+    }
+
+    /// Sort `todos` in place. The sort is stable.
+    pub fn sort(self, todos: &mut [Todo]) {
+        // `Reverse(is_some)` puts unset priorities and due dates last
+        match self {
+            SortOrder::Urgency => todos.sort_by_key(|todo| {
+                let priority = (Reverse(todo.priority.is_some()), todo.priority);
+                let due = (Reverse(todo.due.is_some()), todo.due);
+                (!todo.status.is_open(), priority, due, todo.id)
+            }),
+            SortOrder::Priority => todos.sort_by_key(|todo| (Reverse(todo.priority.is_some()), todo.priority, todo.id)),
+            SortOrder::Due => todos.sort_by_key(|todo| {
+                (Reverse(todo.due.is_some()), todo.due, Reverse(todo.priority.is_some()), todo.priority, todo.id)
+            }),
+            SortOrder::Id => todos.sort_by_key(|todo| todo.id),
+        }
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SortOrder, Error> {
+        SortOrder::ALL
+            .into_iter()
+            .find(|order| order.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = SortOrder::ALL.iter().map(|order| order.as_str()).collect();
+                Error::Validation(format!("unknown sort order `{}` (expected one of: {})", s, names.join(", ")))
+            })
+    }
+}
+
+/// A comma-separated list of numbers and inclusive ranges, e.g. `1,3,5-9`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberList(pub Vec<RangeInclusive<u64>>);
+
+impl FromStr for NumberList {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<NumberList, Error> {
+        let number = |n: &str| {
+            n.trim().parse::<u64>().map_err(|_| Error::Validation(format!("`{}` is not a number", n.trim())))
+        };
+        s.split(',')
+            .map(|part| match part.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (number(start)?, number(end)?);
+                    if start > end {
+                        return Err(Error::Validation(format!("the range {}-{} is backwards", start, end)));
+                    }
+                    Ok(start..=end)
+                }
+                None => number(part).map(|n| n..=n),
+            })
+            .collect::<Result<_, _>>()
+            .map(NumberList)
+    }
+}
+
+/// The tasks a command works on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selection {
+    /// By their stable IDs.
+    Ids(NumberList),
+    /// By their 0-based positions in the order the tasks were added.
+    Positions(NumberList),
+    /// Every task matching a filter expression, e.g. `status:open and tag:work`.
+    Matching(String),
+}
+
+/// A todo list backed by a `TodoStore`.
+///
+/// Changes are buffered by the store until `save` is called, so several
+/// operations can be grouped into a single write. Dropping the list releases
+/// the store's lock; unsaved changes are discarded.
+///
+/// A list opened with `open` records each save in a [`Journal`] next to the
+/// data file, so it can be undone and redone.
+///
+/// ```no_run
+/// use cli_todo_rust::{config, TodoList};
+///
+/// let config = config::load_config()?;
+/// let path = config::data_file_path(None, &config)?;
+/// let mut list = TodoList::open(&config, &path, true)?;
+/// let todo = list.add("Buy groceries")?;
+/// list.complete(todo.id)?;
+/// list.save()?;
+/// # Ok::<(), cli_todo_rust::Error>(())
+/// ```
+pub struct TodoList {
+    store: Box<dyn TodoStore>,
+    /// Where saves are recorded for undo, if anywhere.
+    journal_path: Option<PathBuf>,
+    /// Unsaved changes, one per todo.
+    changes: Vec<Change>,
+    /// A journal whose cursor was moved by undo or redo, to write on save.
+    replayed: Option<Journal>,
+}
+
+impl TodoList {
+    /// Wrap an already opened store. Changes are not journaled unless
+    /// `with_journal` is used as well.
+    pub fn new(store: Box<dyn TodoStore>) -> TodoList {
+        TodoList { store, journal_path: None, changes: Vec::new(), replayed: None }
+    }
+
+    /// Record saves in the journal at `path`, enabling undo and redo.
+    pub fn with_journal(mut self, path: impl Into<PathBuf>) -> TodoList {
+        self.journal_path = Some(path.into());
+        self
+    }
+
+    /// Open the list at `path` with the backend chosen in `config`, with its
+    /// journal. Pass `exclusive` when the list will be changed; read-only
+    /// users can share the lock with each other.
+    pub fn open(config: &Config, path: &Path, exclusive: bool) -> Result<TodoList, Error> {
+        Ok(TodoList::new(store::open(config.backend, path, exclusive)?).with_journal(Journal::path_for(path)))
+    }
+
+    /// Add a new todo and return it with its freshly assigned ID.
+    pub fn add(&mut self, description: impl Into<String>) -> Result<Todo, Error> {
+        self.add_todo(Todo::new(description))
+    }
+
+    /// Add a todo with its fields already filled in (its ID is ignored) and
+    /// return it with its freshly assigned ID. `created_at` and `updated_at`
+    /// are set to now unless the todo already has them.
+    pub fn add_todo(&mut self, mut todo: Todo) -> Result<Todo, Error> {
+        todo.created_at.get_or_insert(Timestamp::now());
+        self.import(todo)
+    }
+
+    /// Add a todo read from an export or another tool and return it with
+    /// its freshly assigned ID. Unlike `add_todo`, a missing `created_at`
+    /// stays unknown; only `updated_at` is set to now if it is missing.
+    ///
+    /// The ID `todo` comes with is ignored, but its `parent` must already
+    /// name a stored task.
+    pub fn import(&mut self, mut todo: Todo) -> Result<Todo, Error> {
+        // The ID it had elsewhere may be a stored task's, or its new parent's
+        todo.id = 0;
+        todo.validate()?;
+        self.check_parent(&todo)?;
+        todo.updated_at.get_or_insert(Timestamp::now());
+        let todo = self.store.insert(todo)?;
+        self.record(todo.id, None, Some(todo.clone()));
+        Ok(todo)
+    }
+
+    /// Replace the stored todo that has the same ID as `todo`, after
+    /// checking that the new version is valid, and return it as stored (with
+    /// its timestamps brought up to date).
+    pub fn edit(&mut self, todo: &Todo) -> Result<Todo, Error> {
+        todo.validate()?;
+        self.check_parent(todo)?;
+        let before = self.get(todo.id)?;
+        let mut todo = todo.clone();
+        touch(&before, &mut todo);
+        self.store.update(&todo)?;
+        self.record(todo.id, Some(before), Some(todo.clone()));
+        Ok(todo)
+    }
+
+    /// Mark the todo with the given ID as done and return it.
+    pub fn complete(&mut self, id: u64) -> Result<Todo, Error> {
+        self.set_status(id, Status::Done)
+    }
+
+    /// Move the todo with the given ID to `status` and return it.
+    pub fn set_status(&mut self, id: u64, status: Status) -> Result<Todo, Error> {
+        let before = self.get(id)?;
+        let mut todo = before.clone();
+        todo.status = status;
+        touch(&before, &mut todo);
+        self.store.update(&todo)?;
+        self.record(id, Some(before), Some(todo.clone()));
+        Ok(todo)
+    }
+
+    /// Remove the todo with the given ID and return it. Its subtasks become
+    /// top-level tasks.
+    pub fn remove(&mut self, id: u64) -> Result<Todo, Error> {
+        let todo = self.store.delete(id)?;
+        self.record(id, Some(todo.clone()), None);
+        for mut subtask in self.store.subtasks(id)? {
+            subtask.parent = None;
+            self.edit(&subtask)?;
+        }
+        Ok(todo)
+    }
+
+    /// Revert the most recent saved change that hasn't been undone, and
+    /// return it; `None` if there is nothing to undo. Takes effect on `save`.
+    ///
+    /// Fails without changing anything if a task involved was changed
+    /// since by something that didn't go through the journal.
+    pub fn undo(&mut self) -> Result<Option<Entry>, Error> {
+        let mut journal = self.journal_for_replay()?;
+        let Some(entry) = journal.undoable().cloned() else {
+            return Ok(None);
+        };
+        let steps = entry.changes.iter().rev().map(|change| (change.id, &change.after, &change.before));
+        self.replay(steps.collect(), "undone")?;
+        journal.step_back();
+        self.replayed = Some(journal);
+        Ok(Some(entry))
+    }
+
+    /// Re-apply the most recently undone change, and return it; `None` if
+    /// there is nothing to redo. Takes effect on `save`.
+    pub fn redo(&mut self) -> Result<Option<Entry>, Error> {
+        let mut journal = self.journal_for_replay()?;
+        let Some(entry) = journal.redoable().cloned() else {
+            return Ok(None);
+        };
+        let steps = entry.changes.iter().map(|change| (change.id, &change.before, &change.after));
+        self.replay(steps.collect(), "redone")?;
+        journal.step_forward();
+        self.replayed = Some(journal);
+        Ok(Some(entry))
+    }
+
+    /// The journal of saved changes, most recent last.
+    pub fn history(&self) -> Result<Journal, Error> {
+        match &self.journal_path {
+            Some(path) => Journal::load(path),
+            None => Err(Error::Validation("this list doesn't keep a journal".to_string())),
+        }
+    }
+
+    /// The todo with the given ID.
+    pub fn get(&self, id: u64) -> Result<Todo, Error> {
+        self.store.get(id)?.ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+
+    /// The ID of the todo at a 0-based position in the list.
+    pub fn id_at(&self, index: usize) -> Result<u64, Error> {
+        self.store.load()?
+            .get(index)
+            .map(|todo| todo.id)
+            .ok_or_else(|| Error::NotFound(format!("at position {}", index)))
+    }
+
+    /// The IDs of the tasks `selection` points at, in the order given and
+    /// without duplicates.
+    ///
+    /// Everything is resolved against one snapshot of the list before any task
+    /// is changed: positions shift as tasks are removed, so `remove --index 0,1`
+    /// must not be applied one position at a time. A single ID or position that
+    /// doesn't exist is an error; an ID range only has to contain one task,
+    /// since removed IDs leave gaps.
+    pub fn resolve(&self, selection: &Selection) -> Result<Vec<u64>, Error> {
+        let todos = self.store.load()?;
+        let mut ids = Vec::new();
+        match selection {
+            Selection::Ids(NumberList(ranges)) => {
+                for range in ranges {
+                    let found: Vec<u64> = todos.iter().map(|todo| todo.id).filter(|id| range.contains(id)).collect();
+                    if found.is_empty() {
+                        return Err(Error::NotFound(if range.start() == range.end() {
+                            range.start().to_string()
+                        } else {
+                            format!("with an ID in {}-{}", range.start(), range.end())
+                        }));
+                    }
+                    ids.extend(found);
+                }
+            }
+            Selection::Positions(NumberList(ranges)) => {
+                for range in ranges {
+                    if *range.end() >= todos.len() as u64 {
+                        return Err(Error::NotFound(format!("at position {}", range.end())));
+                    }
+                    ids.extend(range.clone().map(|index| todos[index as usize].id));
+                }
+            }
+            Selection::Matching(expr) => {
+                let filter = Filter::parse(expr, Date::today())?;
+                ids.extend(todos.iter().filter(|todo| filter.matches(todo)).map(|todo| todo.id));
+                if ids.is_empty() {
+                    return Err(Error::NotFound(format!("matching `{}`", expr)));
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        ids.retain(|id| seen.insert(*id));
+        Ok(ids)
+    }
+
+    /// Every todo that matches `filter`, in the order they were added.
+    pub fn query(&self, filter: impl Fn(&Todo) -> bool) -> Result<Vec<Todo>, Error> {
+        self.store.query(&filter)
+    }
+
+    /// Every tag in use, with the number of todos that carry it.
+    pub fn tag_counts(&self) -> Result<BTreeMap<String, usize>, Error> {
+        let mut counts = BTreeMap::new();
+        for todo in self.store.load()? {
+            for tag in todo.tags {
+                *counts.entry(tag).or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Every project in use, with the number of todos that belong to it.
+    pub fn project_counts(&self) -> Result<BTreeMap<String, usize>, Error> {
+        let mut counts = BTreeMap::new();
+        for project in self.store.load()?.into_iter().filter_map(|todo| todo.project) {
+            *counts.entry(project).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Every todo, in the order they were added.
+    pub fn all(&self) -> Result<Vec<Todo>, Error> {
+        self.store.load()
+    }
+
+    /// Write all changes made since the list was opened, and record them in
+    /// the journal.
+    pub fn save(&mut self) -> Result<(), Error> {
+        self.store.commit()?;
+
+        let changes = std::mem::take(&mut self.changes);
+        let mut journal = match (self.replayed.take(), &self.journal_path) {
+            (Some(journal), _) => journal,
+            (None, Some(path)) if !changes.is_empty() => Journal::load(path)?,
+            _ => return Ok(()),
+        };
+        if !changes.is_empty() {
+            journal.record(Entry { time: Timestamp::now(), changes });
+        }
+        // The list itself is already saved at this point
+        journal.save().map_err(|e| {
+            Error::Io(std::io::Error::other(format!("the change was saved, but recording it for undo failed: {}", e)))
+        })
+    }
+
+    /// Note that todo `id` went from `before` to `after`, merging with an
+    /// earlier unsaved change to the same todo.
+    fn record(&mut self, id: u64, before: Option<Todo>, after: Option<Todo>) {
+        match self.changes.iter().position(|change| change.id == id) {
+            Some(i) => {
+                self.changes[i].after = after;
+                if self.changes[i].before == self.changes[i].after {
+                    self.changes.remove(i);
+                }
+            }
+            None if before != after => self.changes.push(Change { id, before, after }),
+            None => {}
+        }
+    }
+
+    /// Check that a todo's parent exists and isn't the todo itself or one of
+    /// its subtasks, so that subtasks always form a tree.
+    fn check_parent(&self, todo: &Todo) -> Result<(), Error> {
+        let mut parent = todo.parent;
+        while let Some(id) = parent {
+            if id == todo.id {
+                return Err(Error::Validation(format!("task {} can't be a subtask of itself or of its own subtasks", todo.id)));
+            }
+            let Some(found) = self.store.get(id)? else {
+                return Err(Error::Validation(format!("the parent task {} doesn't exist", id)));
+            };
+            parent = found.parent;
+        }
+        Ok(())
+    }
+
+    /// The journal, for undo or redo. Those work on saved changes only.
+    fn journal_for_replay(&mut self) -> Result<Journal, Error> {
+        if !self.changes.is_empty() || self.replayed.is_some() {
+            return Err(Error::Validation("save the list before undoing or redoing".to_string()));
+        }
+        self.history()
+    }
+
+    /// Move each todo from its `from` state to its `to` state, after
+    /// checking that every todo is still in its `from` state.
+    fn replay(&mut self, steps: Vec<(u64, &Option<Todo>, &Option<Todo>)>, done: &str) -> Result<(), Error> {
+        for &(id, from, _) in &steps {
+            if self.store.get(id)? != *from {
+                return Err(Error::Validation(format!(
+                    "task {} was changed outside of the journal since then, so this can't be {}",
+                    id, done
+                )));
+            }
+        }
+        for (id, from, to) in steps {
+            match (from, to) {
+                (Some(_), Some(todo)) => self.store.update(todo)?,
+                (Some(_), None) => {
+                    self.store.delete(id)?;
+                }
+                (None, Some(todo)) => self.store.restore(todo)?,
+                (None, None) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bring the timestamps of `todo` up to date after it changed from `before`:
+/// `updated_at` becomes now, and `completed_at` is set when the todo was just
+/// marked as done and cleared when it no longer is. Nothing changes if the
+/// todo didn't.
+fn touch(before: &Todo, todo: &mut Todo) {
+    if todo == before {
+        return;
+    }
+    let now = Timestamp::now();
+    todo.updated_at = Some(now);
+    if todo.status != Status::Done {
+        todo.completed_at = None;
+    } else if before.status != Status::Done {
+        todo.completed_at.get_or_insert(now);
+    }
+}
@@ -1,23 +1,42 @@
-use clap::{Parser, Subcommand};
-use std::fs;
-use serde_json;
-use serde::{Deserialize, Serialize}; 
-use std::path::Path;
+//! Command-line front end: parses arguments, calls into the
+//! `cli_todo_rust` library and prints the results.
 
-/// Data model representing a single todo item.
-/// Derives Serialize/Deserialize for JSON persistence.
-#[derive(Debug, Serialize, Deserialize)]
-struct Todo {
-    description: String,
-    completed: bool
-}
+use clap::{Args, Parser, Subcommand};
+use cli_todo_rust::config::{data_file_path, load_config};
+use cli_todo_rust::store::{self, Backend};
+use cli_todo_rust::filter::Filter;
+use cli_todo_rust::journal::Entry;
+use cli_todo_rust::csv::{self, Field};
+use cli_todo_rust::{
+    edit, ical, markdown, taskwarrior, todotxt, Date, Error, NumberList, Priority, Selection, SortOrder, Status,
+    Timestamp, Todo, TodoList,
+};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+use std::str::FromStr;
 
 /// Main CLI structure that holds subcommands.
 /// The Parser derive macro enables automatic CLI argument parsing via clap.
 #[derive(Parser)]
 struct Cli {
-    /// Available subcommands (Add, Remove, List, Complete).
-    /// The command field is automatically populated by clap based on user input.
+    /// Todo file to use, overriding TODO_FILE, the config file and the XDG default
+    #[arg(long, global = true, value_name = "PATH")]
+    file: Option<PathBuf>,
+
+    /// How to print results and errors: text, json (one document) or jsonl
+    /// (one JSON object per line)
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "text")]
+    output: OutputFormat,
+
+    /// The command to run, one of `Commands`; clap fills it in from the
+    /// first positional argument.
     #[command(subcommand)]
     command: Commands,
 }
@@ -26,102 +45,1072 @@ struct Cli {
 /// Each variant maps to a user-facing command (e.g., "todo add", "todo list").
 #[derive(Subcommand)]
 enum Commands {
-    /// Add a new todo with the given description
+    /// Add a new todo; `+tag` and `@project` words in the description are
+    /// stored as its tags and project
     Add {
-        description: String
+        description: String,
+        /// When the task is due: a date (2026-11-01) or a phrase like
+        /// "tomorrow", "friday", "next week" or "in 3 days"
+        #[arg(long, value_name = "WHEN")]
+        due: Option<String>,
+        /// How important the task is: H, M or L (or high, medium, low)
+        #[arg(short, long)]
+        priority: Option<String>,
+        /// Make the task a subtask of the task with this ID
+        #[arg(long, value_name = "ID")]
+        parent: Option<u64>,
     },
-    /// Remove a todo by its index (0-based)
+    /// Remove todos by ID, position or filter
     Remove {
-        index: usize
+        #[command(flatten)]
+        target: TaskRef
     },
-    /// List all todos with their completion status
-    List,
-    /// Mark a todo as completed by its index (0-based)
-    /// Note: usize is Rust's natural indexing type for arrays/vectors
+    /// List all todos with their ID, status, priority and due date
+    List {
+        /// urgency (open, high-priority work first), priority, due or id
+        #[arg(long, value_name = "ORDER", default_value = "urgency")]
+        sort: SortOrder,
+        /// Only show todos with this tag (repeat to require several)
+        #[arg(long)]
+        tag: Vec<String>,
+        /// Only show todos in this project
+        #[arg(long)]
+        project: Option<String>,
+        /// Only show todos matching a filter expression, e.g. "status:open and tag:work"
+        #[arg(long, value_name = "EXPR")]
+        filter: Option<String>,
+        /// Also show when each todo was created, last updated and completed
+        #[arg(short, long)]
+        long: bool,
+    },
+    /// List every tag in use, with how many todos carry it
+    Tags,
+    /// List every project in use, with how many todos belong to it
+    Projects,
+    /// Mark todos as done by ID, position or filter (e.g. `complete 1,3,5-9`)
     Complete {
-        index: usize
+        #[command(flatten)]
+        target: TaskRef
+    },
+    /// Mark a todo as in progress
+    Start {
+        #[command(flatten)]
+        target: TaskRef
+    },
+    /// Mark a todo as blocked
+    Block {
+        #[command(flatten)]
+        target: TaskRef
+    },
+    /// Move a done, cancelled, started or blocked todo back to "todo"
+    Reopen {
+        #[command(flatten)]
+        target: TaskRef
+    },
+    /// Mark a todo as cancelled (kept in the list, but closed)
+    Cancel {
+        #[command(flatten)]
+        target: TaskRef
+    },
+    /// Change a todo's fields; with no field options, open it in $EDITOR
+    Edit {
+        #[command(flatten)]
+        target: TaskRef,
+        #[command(flatten)]
+        fields: EditFields,
+    },
+    /// Revert the last change (add, remove, complete, edit...)
+    Undo,
+    /// Re-apply the last change that was undone
+    Redo,
+    /// Show recent changes, newest first
+    History {
+        /// How many changes to show
+        #[arg(short = 'n', long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Print todos as JSON or in another format
+    Export {
+        /// json (as stored), todotxt, ical, csv, tsv, markdown or taskwarrior
+        #[arg(long, default_value = "json")]
+        format: FileFormat,
+        /// Only export todos matching a filter expression
+        #[arg(long, value_name = "EXPR")]
+        filter: Option<String>,
+        /// The columns of a csv or tsv export, e.g. id,description,due
+        /// (default: every field)
+        #[arg(long, value_name = "FIELDS")]
+        columns: Option<ColumnList>,
+    },
+    /// Add the todos from a file written by `export` or another tool; they get
+    /// new IDs, except that iCalendar and Taskwarrior tasks update the ones
+    /// with the same UID
+    Import {
+        /// json (as written by `export`), todotxt, ical (merged by UID), csv,
+        /// tsv, markdown or taskwarrior (merged by UUID)
+        #[arg(long, default_value = "json")]
+        format: FileFormat,
+        /// File to read; standard input if omitted or `-`
+        #[arg(value_name = "FILE")]
+        input: Option<PathBuf>,
+        /// Read a csv or tsv column into a field, e.g. "Task name=description"
+        /// (repeatable); columns named like a field are read without one
+        #[arg(long, value_name = "COLUMN=FIELD")]
+        map: Vec<ColumnMapping>,
+        /// Show what would be imported without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Reconcile a Markdown task list with the todos both ways: new items
+    /// are added as tasks, and status changes go whichever way is newer
+    Sync {
+        /// The Markdown file, e.g. PLAN.md
+        #[arg(value_name = "FILE")]
+        markdown: PathBuf,
+    },
+    /// Check the todo file and recover what can be saved from a damaged one
+    #[command(alias = "repair")]
+    Doctor,
+    /// Fold the event log into a snapshot (events backend only)
+    Compact,
+}
+
+/// Identifies the tasks a command works on: by their stable IDs, by their
+/// positions, or every task matching a filter.
+/// Exactly one of the three must be given; the group enforces that.
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+struct TaskRef {
+    /// IDs of the tasks, as shown by `list`: one ID, or a list with ranges like 1,3,5-9
+    #[arg(value_name = "IDS")]
+    id: Option<NumberList>,
+    /// Address the tasks by their 0-based positions in the order tasks were
+    /// added (as shown by `list --sort id`) instead of their IDs, e.g. 0 or 0,2-4
+    #[arg(long, value_name = "POSITIONS")]
+    index: Option<NumberList>,
+    /// Every task matching a filter expression, e.g. "status:open and due:<today"
+    #[arg(long, value_name = "EXPR")]
+    filter: Option<String>,
+}
+
+impl TaskRef {
+    /// The tasks given, for `TodoList::resolve`.
+    fn selection(&self) -> Selection {
+        match (&self.id, &self.index, &self.filter) {
+            (Some(ids), _, _) => Selection::Ids(ids.clone()),
+            (None, Some(positions), _) => Selection::Positions(positions.clone()),
+            (None, None, Some(expr)) => Selection::Matching(expr.clone()),
+            (None, None, None) => unreachable!("the group requires one of them"),
+        }
+    }
+}
+
+/// A comma-separated list of fields, e.g. `id,description,due`.
+#[derive(Clone)]
+struct ColumnList(Vec<Field>);
+
+impl FromStr for ColumnList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColumnList, String> {
+        s.split(',').map(|name| name.parse().map_err(|e: Error| e.to_string())).collect::<Result<_, _>>().map(ColumnList)
     }
 }
 
-/// Load todos from JSON file, creating an empty file if none exists.
-/// 
-/// Returns a Result containing either:
-/// - Ok(Vec<Todo>): Successfully loaded todos from file
-/// - Err: File system or deserialization error
-fn load_data() -> Result<Vec<Todo>, Box<dyn std::error::Error>> {
-    let folder_name = "storage";
-    let file_path = format!("{}/todo-file.json", folder_name);
+/// A CSV header name and the field its column is read into, e.g.
+/// `Task name=description`.
+#[derive(Clone)]
+struct ColumnMapping(String, Field);
 
-    let list = if Path::new(&file_path).exists() {
-        // File exists - read and deserialize
-        let data = fs::read_to_string(&file_path)?;
-        serde_json::from_str(&data)?
+impl FromStr for ColumnMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColumnMapping, String> {
+        // Field names never contain `=`, header names might
+        let Some((column, field)) = s.rsplit_once('=') else {
+            return Err(format!("`{}` is not COLUMN=FIELD", s));
+        };
+        let field = field.parse().map_err(|e: Error| e.to_string())?;
+        Ok(ColumnMapping(column.to_string(), field))
+    }
+}
+
+/// How results are printed, chosen with `--output`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// Human-readable lines.
+    Text,
+    /// One pretty-printed JSON document per command.
+    Json,
+    /// One compact JSON object per line: one per record for commands that
+    /// return a list (tasks, tags, history entries), otherwise just one.
+    Jsonl,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            _ => Err(format!("unknown output format `{}` (expected text, json or jsonl)", s)),
+        }
+    }
+}
+
+/// A file format `export` writes and `import` reads.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FileFormat {
+    /// A JSON array of todos, as they are stored.
+    Json,
+    /// todo.txt, one task per line.
+    Todotxt,
+    /// iCalendar, one VTODO per task.
+    Ical,
+    /// Comma-separated values with a header row, one task per row.
+    Csv,
+    /// Tab-separated values with a header row, one task per row.
+    Tsv,
+    /// A Markdown task list, with subtasks nested under their parents.
+    Markdown,
+    /// Taskwarrior's `task export` JSON.
+    Taskwarrior,
+}
+
+impl FileFormat {
+    fn as_str(self) -> &'static str {
+        match self {
+            FileFormat::Json => "json",
+            FileFormat::Todotxt => "todotxt",
+            FileFormat::Ical => "ical",
+            FileFormat::Csv => "csv",
+            FileFormat::Tsv => "tsv",
+            FileFormat::Markdown => "markdown",
+            FileFormat::Taskwarrior => "taskwarrior",
+        }
+    }
+
+    /// The cell separator of csv and tsv, `None` for the other formats.
+    fn delimiter(self) -> Option<char> {
+        match self {
+            FileFormat::Csv => Some(','),
+            FileFormat::Tsv => Some('\t'),
+            FileFormat::Json | FileFormat::Todotxt | FileFormat::Ical | FileFormat::Markdown | FileFormat::Taskwarrior => {
+                None
+            }
+        }
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FileFormat, String> {
+        match s {
+            "json" => Ok(FileFormat::Json),
+            "todotxt" => Ok(FileFormat::Todotxt),
+            "ical" | "ics" => Ok(FileFormat::Ical),
+            "csv" => Ok(FileFormat::Csv),
+            "tsv" => Ok(FileFormat::Tsv),
+            "markdown" | "md" => Ok(FileFormat::Markdown),
+            "taskwarrior" | "tw" => Ok(FileFormat::Taskwarrior),
+            _ => Err(format!(
+                "unknown format `{}` (expected json, todotxt, ical, csv, tsv, markdown or taskwarrior)",
+                s
+            )),
+        }
+    }
+}
+
+/// What a command did, ready to be printed in any output format.
+///
+/// In JSON, every command prints an object with `"ok": true`, the command's
+/// name and the fields in `data`; failures print `"ok": false` and an
+/// `error` object instead (see `print_error`).
+struct Report {
+    /// The command's name, e.g. `list`.
+    command: &'static str,
+    /// The command-specific fields of the JSON object.
+    data: Map<String, Value>,
+    /// The field of `data` holding the records `jsonl` prints one per line,
+    /// for commands that return a list.
+    records: Option<&'static str>,
+    /// What `text` output prints, one entry per line.
+    lines: Vec<String>,
+}
+
+impl Report {
+    /// A report with the JSON fields in `data`, which must be an object.
+    fn new(command: &'static str, data: Value) -> Report {
+        let Value::Object(data) = data else {
+            unreachable!("report data is always built with json!({{...}})")
+        };
+        Report { command, data, records: None, lines: Vec::new() }
+    }
+
+    /// Mark the `field` array as the records `jsonl` streams.
+    fn records(mut self, field: &'static str) -> Report {
+        self.records = Some(field);
+        self
+    }
+
+    /// Add a line of text output.
+    fn line(&mut self, line: impl Into<String>) {
+        self.lines.push(line.into());
+    }
+
+    /// Print the report to stdout. A reader that stopped early, like
+    /// `list | head -1`, is not an error: the rest is just dropped.
+    fn print(self, format: OutputFormat) -> Result<(), Error> {
+        let mut object = Map::new();
+        object.insert("ok".to_string(), Value::Bool(true));
+        object.insert("command".to_string(), Value::from(self.command));
+        let mut out = io::stdout().lock();
+        let write = || -> Result<(), Error> {
+            match format {
+                OutputFormat::Text => {
+                    for line in &self.lines {
+                        writeln!(out, "{}", line)?;
+                    }
+                }
+                OutputFormat::Json => {
+                    object.extend(self.data);
+                    writeln!(out, "{}", serde_json::to_string_pretty(&object)?)?;
+                }
+                OutputFormat::Jsonl => match self.records.and_then(|field| self.data.get(field)) {
+                    Some(Value::Array(records)) => {
+                        for record in records {
+                            writeln!(out, "{}", serde_json::to_string(record)?)?;
+                        }
+                    }
+                    _ => {
+                        object.extend(self.data);
+                        writeln!(out, "{}", serde_json::to_string(&object)?)?;
+                    }
+                },
+            }
+            Ok(out.flush()?)
+        };
+        match write() {
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Report a failure in `format`: `Error: ...` on stderr for text, or on
+/// stdout as `{"ok": false, "error": {"kind", "message", "exit_code"}}`.
+fn print_error(format: OutputFormat, kind: &str, message: &str, exit_code: i32) {
+    let error = json!({
+        "ok": false,
+        "error": { "kind": kind, "message": message, "exit_code": exit_code },
+    });
+    match format {
+        OutputFormat::Text => eprintln!("Error: {}", message),
+        // Nothing more can be reported if stdout is gone too
+        OutputFormat::Json => {
+            let _ = writeln!(io::stdout(), "{}", serde_json::to_string_pretty(&error).unwrap_or_default());
+        }
+        OutputFormat::Jsonl => {
+            let _ = writeln!(io::stdout(), "{}", error);
+        }
+    }
+}
+
+/// The `--output` format asked for on a command line that clap rejected,
+/// so even usage errors can be reported as JSON.
+fn requested_format(args: &[OsString]) -> OutputFormat {
+    let mut format = OutputFormat::Text;
+    for (i, arg) in args.iter().enumerate() {
+        // Arguments that aren't UTF-8 (such as some paths) can't be `--output`
+        let value = match arg.to_str().and_then(|arg| arg.strip_prefix("--output")) {
+            Some("") => args.get(i + 1).and_then(|value| value.to_str()),
+            Some(rest) => rest.strip_prefix('='),
+            None => None,
+        };
+        if let Some(value) = value.and_then(|value| value.parse().ok()) {
+            format = value;
+        }
+    }
+    format
+}
+
+/// A journal entry as it appears in JSON output.
+fn entry_json(entry: &Entry) -> Value {
+    json!({ "time": entry.time, "summary": entry.summary(), "changes": entry.changes })
+}
+
+/// Fields that `edit` can change directly from the command line.
+#[derive(Args)]
+struct EditFields {
+    /// New description for the task
+    #[arg(short, long)]
+    description: Option<String>,
+    /// New due date (same forms as `add --due`), or "none" to clear it
+    #[arg(long, value_name = "WHEN")]
+    due: Option<String>,
+    /// New priority (H, M or L), or "none" to clear it
+    #[arg(short, long)]
+    priority: Option<String>,
+    /// Replace the tags with this comma-separated list, or "none" to clear them
+    #[arg(long, value_name = "TAGS")]
+    tags: Option<String>,
+    /// New project, or "none" to clear it
+    #[arg(long)]
+    project: Option<String>,
+    /// ID of the task this becomes a subtask of, or "none" to make it a
+    /// top-level task
+    #[arg(long, value_name = "ID")]
+    parent: Option<String>,
+}
+
+impl EditFields {
+    /// True when no field was given, which means "open the editor".
+    fn is_empty(&self) -> bool {
+        self.description.is_none()
+            && self.due.is_none()
+            && self.priority.is_none()
+            && self.tags.is_none()
+            && self.project.is_none()
+            && self.parent.is_none()
+    }
+
+    fn apply(self, todo: &mut Todo) -> Result<(), Error> {
+        if let Some(description) = self.description {
+            todo.description = description;
+        }
+        if let Some(due) = self.due {
+            todo.due = match due.trim() {
+                "none" | "" => None,
+                due => Some(Date::parse(due, Date::today())?),
+            };
+        }
+        if let Some(priority) = self.priority {
+            todo.priority = match priority.trim() {
+                "none" | "" => None,
+                priority => Some(priority.parse()?),
+            };
+        }
+        if let Some(tags) = self.tags {
+            todo.tags = match tags.trim() {
+                "none" | "" => Vec::new(),
+                tags => tags.split(',').map(|tag| tag.trim().trim_start_matches('+').to_string()).collect(),
+            };
+        }
+        if let Some(project) = self.project {
+            todo.project = match project.trim() {
+                "none" | "" => None,
+                project => Some(project.trim_start_matches('@').to_string()),
+            };
+        }
+        if let Some(parent) = self.parent {
+            todo.parent = match parent.trim() {
+                "none" | "" => None,
+                parent => Some(parent.parse().map_err(|_| Error::Validation(format!("`{}` is not a task ID", parent)))?),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// Let the user edit `todo` as TOML in $VISUAL / $EDITOR (default `vi`).
+/// Returns `None` if they cancelled by emptying the file.
+///
+/// The temp file is removed afterwards, except when the result doesn't
+/// validate: then it is kept so the user's typing isn't lost.
+fn edit_in_editor(todo: &Todo) -> Result<Option<Todo>, Error> {
+    let (path, mut file) = create_temp_file(todo.id)?;
+    file.write_all(edit::to_toml(todo).as_bytes())?;
+    drop(file);
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Go through the shell so editors with arguments (e.g. "code --wait") work
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status()?;
+    if !status.success() {
+        let _ = fs::remove_file(&path);
+        return Err(Error::Io(io::Error::other(format!("editor `{}` exited with {}", editor, status))));
+    }
+
+    let text = fs::read_to_string(&path)?;
+    match edit::from_toml(todo, &text) {
+        Ok(edited) => {
+            let _ = fs::remove_file(&path);
+            Ok(edited)
+        }
+        Err(e) => Err(Error::Validation(format!("{} (your edits are kept in {})", e, path.display()))),
+    }
+}
+
+/// Create a temp file only we can read for editing task `id`. The name is
+/// easy to guess, so the file must be new: `create_new` won't follow a
+/// symlink or open a file someone else put there, and another name is
+/// tried instead.
+fn create_temp_file(id: u64) -> Result<(PathBuf, fs::File), Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut attempt = 0;
+    loop {
+        let path = env::temp_dir().join(format!("cli-todo-rust-{}-{}-{}.toml", std::process::id(), id, attempt));
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => {
+                return Err(Error::Io(io::Error::new(e.kind(), format!("cannot create {}: {}", path.display(), e))))
+            }
+        }
+    }
+}
+
+/// How `list` shows a task, e.g. `3: Pay rent +home [ ] (priority H, due 2026-10-20)`.
+fn todo_line(todo: &Todo, today: Date, color: bool) -> String {
+    // The checkbox says it all for the two common states
+    let mut notes = Vec::new();
+    if !matches!(todo.status, Status::Todo | Status::Done) {
+        notes.push(todo.status.to_string());
+    }
+    if let Some(priority) = todo.priority {
+        notes.push(format!("priority {}", priority));
+    }
+    notes.extend(due_note(todo, today, color));
+    if let Some(parent) = todo.parent {
+        notes.push(format!("subtask of {}", parent));
+    }
+
+    let mut line = format!("{}: {}", todo.id, todo.description);
+    for tag in &todo.tags {
+        line.push_str(&format!(" +{}", tag));
+    }
+    if let Some(project) = &todo.project {
+        line.push_str(&format!(" @{}", project));
+    }
+    line.push_str(&format!(" {}", todo.status.marker()));
+    if !notes.is_empty() {
+        line.push_str(&format!(" ({})", notes.join(", ")));
+    }
+    line
+}
+
+/// How `list` shows a task's due date, relative to `today`: overdue and
+/// due-today tasks are called out (in red and yellow on a terminal).
+fn due_note(todo: &Todo, today: Date, color: bool) -> Option<String> {
+    let due = todo.due?;
+    let (note, ansi) = if todo.is_overdue(today) {
+        (format!("overdue, due {}", due), "31")
+    } else if due == today && todo.status.is_open() {
+        ("due today".to_string(), "33")
     } else {
-        // File doesn't exist - create storage directory and empty JSON file
-        fs::create_dir_all(folder_name)?;
-        let empty = Vec::<Todo>::new();
-        let json = serde_json::to_string(&empty)?;
-        fs::write(&file_path, json)?;
-        empty
+        return Some(format!("due {}", due));
     };
+    Some(if color { format!("\x1b[{}m{}\x1b[0m", ansi, note) } else { note })
+}
 
-    Ok(list)
+/// The second line `list --long` prints for a task, e.g.
+/// `created 2026-10-15T09:12:00Z, updated ..., completed ...`.
+fn timestamps_note(todo: &Todo) -> String {
+    let show = |timestamp: Option<Timestamp>| timestamp.map_or("unknown".to_string(), |t| t.to_string());
+    let mut note = format!("created {}, updated {}", show(todo.created_at), show(todo.updated_at));
+    if todo.status == Status::Done {
+        note.push_str(&format!(", completed {}", show(todo.completed_at)));
+    }
+    note
 }
 
-/// Save todos to JSON file with pretty formatting.
-/// 
-/// Uses serde_json::to_string_pretty for human-readable output.
-/// Takes a reference to avoid taking ownership of the todo list.
-fn save_todos(list: &Vec<Todo>) -> Result<(), Box<dyn std::error::Error>> {
-    let folder_name = "storage";
-    let file_path = format!("{}/todo-file.json", folder_name);
+/// Parse a filter expression given on the command line, if any.
+fn parse_filter(expr: Option<&str>) -> Result<Option<Filter>, Error> {
+    expr.map(|expr| Filter::parse(expr, Date::today())).transpose()
+}
 
-    // Serialize with indentation for readability
-    let json = serde_json::to_string_pretty(list)?;
-    fs::write(&file_path, json)?;
-    Ok(())
+/// Like `TodoList::resolve`, for commands that work on exactly one task.
+fn resolve_one(list: &TodoList, target: &TaskRef) -> Result<u64, Error> {
+    match list.resolve(&target.selection())?.as_slice() {
+        [id] => Ok(*id),
+        ids => Err(Error::Validation(format!(
+            "{} tasks were given, but this command works on one task at a time",
+            ids.len()
+        ))),
+    }
 }
 
-fn main() {
-    // Parse command-line arguments into Cli struct
-    let cli: Cli = Cli::parse();
-    
-    // Load existing todos or start with empty list if file doesn't exist
-    let mut list: Vec<Todo> = load_data().unwrap_or_default();
+/// Move the targeted tasks to `status`, save, and report the changes,
+/// with a summary line when several tasks were given.
+fn set_status(list: &mut TodoList, target: &TaskRef, command: &'static str, status: Status) -> Result<Report, Error> {
+    let ids = list.resolve(&target.selection())?;
+    let mut changed = Vec::new();
+    let mut unchanged = Vec::new();
+    for &id in &ids {
+        let todo = list.get(id)?;
+        if todo.status == status {
+            unchanged.push(todo);
+        } else {
+            changed.push(list.set_status(id, status)?);
+        }
+    }
+    list.save()?;
+
+    let mut report = Report::new(command, json!({
+        "status": status,
+        "todos": changed.iter().chain(&unchanged).collect::<Vec<_>>(),
+        "changed": changed.iter().map(|todo| todo.id).collect::<Vec<_>>(),
+        "unchanged": unchanged.iter().map(|todo| todo.id).collect::<Vec<_>>(),
+    }))
+    .records("todos");
+    for todo in &changed {
+        match status {
+            Status::Done => report.line(format!("Task '{}' marked as complete!", todo.description)),
+            _ => report.line(format!("Task '{}' is now {}.", todo.description, status)),
+        }
+    }
+    for todo in &unchanged {
+        report.line(format!("Task '{}' was already {}.", todo.description, status));
+    }
+    if ids.len() > 1 {
+        report.line(format!("{} of {} tasks changed to {}.", changed.len(), ids.len(), status));
+    }
+    Ok(report)
+}
+
+/// Run one command and return what it did. Every failure is returned rather
+/// than printed, so `main` can report it and exit with the matching code.
+fn run(cli: Cli) -> Result<Report, Error> {
+    // Decide where the todo file lives before touching it
+    let config = load_config()?;
+    let file_path = data_file_path(cli.file, &config)?;
+
+    // The doctor must work on files that don't load, so it bypasses the list
+    if let Commands::Doctor = cli.command {
+        if !matches!(config.backend, Backend::Json) {
+            return Err(Error::Validation("doctor can only repair the json backend".to_string()));
+        }
+        let message = store::repair_file(&file_path)?;
+        let mut report = Report::new("doctor", json!({ "message": message }));
+        report.line(message);
+        return Ok(report);
+    }
+    if let Commands::Compact = cli.command {
+        if !matches!(config.backend, Backend::Events) {
+            return Err(Error::Validation("compact only applies to the events backend".to_string()));
+        }
+        let compacted = store::EventStore::open(&file_path, true)?.compact()?;
+        let mut report = Report::new("compact", json!({ "compacted": compacted }));
+        report.line(format!("Compacted {} events into the snapshot.", compacted));
+        return Ok(report);
+    }
+
+    // Open (and lock) the list until we return; read-only commands share it.
+    // Load failures are fatal rather than falling back to an empty list.
+    let exclusive = match &cli.command {
+        Commands::List { .. }
+        | Commands::Tags
+        | Commands::Projects
+        | Commands::History { .. }
+        | Commands::Export { .. } => false,
+        // The editor flow takes the lock itself once the user is done
+        Commands::Edit { fields, .. } => !fields.is_empty(),
+        _ => true,
+    };
+    let mut list = TodoList::open(&config, &file_path, exclusive)?;
 
     // Execute the appropriate command based on user input
-    match cli.command {
-        Commands::Add { description } => {
-            list.push(Todo { description, completed: false });
-            let _ = save_todos(&list);
+    let report = match cli.command {
+        Commands::Add { description, due, priority, parent } => {
+            let mut todo = Todo::parse(&description)?;
+            todo.parent = parent;
+            todo.due = due.map(|due| Date::parse(&due, Date::today())).transpose()?;
+            todo.priority = priority.map(|priority| priority.parse::<Priority>()).transpose()?;
+            let todo = list.add_todo(todo)?;
+            list.save()?;
+            let mut report = Report::new("add", json!({ "todo": todo }));
+            report.line(format!("Added task {}: {}", todo.id, todo.description));
+            report
         }
         
-        Commands::Remove { index } => {
-            if index < list.len() {
-                let removed = list.remove(index);
-                println!("Removed: {:?}", removed);
-                let _ = save_todos(&list);
-            } else {
-                eprintln!("Error: Task {} doesn't exist", index);
+        Commands::Remove { target } => {
+            let mut removed = Vec::new();
+            for id in list.resolve(&target.selection())? {
+                removed.push(list.remove(id)?);
             }
+            list.save()?;
+            let mut report = Report::new("remove", json!({ "todos": removed })).records("todos");
+            for todo in &removed {
+                report.line(format!("Removed task {}: {}", todo.id, todo.description));
+            }
+            if removed.len() > 1 {
+                report.line(format!("Removed {} tasks.", removed.len()));
+            }
+            report
         }
         
-        Commands::List => {
-            for (i, todo) in list.iter().enumerate() {
-                let status = if todo.completed { "[x]" } else { "[ ]" };
-                println!("{}: {} {}", i, todo.description, status);
+        Commands::List { sort, tag, project, filter, long } => {
+            let today = Date::today();
+            let color = cli.output == OutputFormat::Text && io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+            let filter = parse_filter(filter.as_deref())?;
+            let mut todos = list.query(|todo| {
+                tag.iter().all(|tag| todo.tags.contains(tag))
+                    && project.as_ref().is_none_or(|project| todo.project.as_ref() == Some(project))
+                    && filter.as_ref().is_none_or(|filter| filter.matches(todo))
+            })?;
+            sort.sort(&mut todos);
+            let mut report = Report::new("list", json!({ "todos": todos })).records("todos");
+            for todo in todos {
+                report.line(todo_line(&todo, today, color));
+                if long {
+                    report.line(format!("    {}", timestamps_note(&todo)));
+                }
             }
+            report
         }
         
-        Commands::Complete { index } => {
-            // get_mut() returns Option<&mut Todo> for safe mutable access
-            if let Some(todo) = list.get_mut(index) {
-                todo.completed = true;
-                println!("Task '{}' marked as complete!", todo.description);
-                let _ = save_todos(&list);
+        Commands::Tags => {
+            let counts = list.tag_counts()?;
+            let tags: Vec<Value> = counts.iter().map(|(name, count)| json!({ "name": name, "count": count })).collect();
+            let mut report = Report::new("tags", json!({ "tags": tags })).records("tags");
+            for (tag, count) in counts {
+                report.line(format!("+{} ({})", tag, count));
+            }
+            report
+        }
+
+        Commands::Projects => {
+            let counts = list.project_counts()?;
+            let projects: Vec<Value> = counts.iter().map(|(name, count)| json!({ "name": name, "count": count })).collect();
+            let mut report = Report::new("projects", json!({ "projects": projects })).records("projects");
+            for (project, count) in counts {
+                report.line(format!("@{} ({})", project, count));
+            }
+            report
+        }
+
+        Commands::Complete { target } => set_status(&mut list, &target, "complete", Status::Done)?,
+
+        Commands::Start { target } => set_status(&mut list, &target, "start", Status::InProgress)?,
+        Commands::Block { target } => set_status(&mut list, &target, "block", Status::Blocked)?,
+        Commands::Reopen { target } => set_status(&mut list, &target, "reopen", Status::Todo)?,
+        Commands::Cancel { target } => set_status(&mut list, &target, "cancel", Status::Cancelled)?,
+
+        Commands::Edit { target, fields } => {
+            let id = resolve_one(&list, &target)?;
+            let original = list.get(id)?;
+            let unchanged = |message: String| {
+                let mut report = Report::new("edit", json!({ "todo": original, "changed": false }));
+                report.line(message);
+                report
+            };
+            let edited = if fields.is_empty() {
+                // Don't keep everyone else locked out while the user types:
+                // release the list, edit, then re-open it to write the result
+                drop(list);
+                let Some(edited) = edit_in_editor(&original)? else {
+                    return Ok(unchanged(format!("Edit cancelled, task {} unchanged.", id)));
+                };
+                list = TodoList::open(&config, &file_path, true)?;
+                if list.get(id)? != original {
+                    return Err(Error::Lock(format!(
+                        "task {} was changed by another process while you were editing it; please try again",
+                        id
+                    )));
+                }
+                edited
+            } else {
+                let mut edited = original.clone();
+                fields.apply(&mut edited)?;
+                edited
+            };
+
+            if edited == original {
+                return Ok(unchanged(format!("No changes to task {}.", id)));
+            }
+            let edited = list.edit(&edited)?;
+            list.save()?;
+            let mut report = Report::new("edit", json!({ "todo": edited, "changed": true }));
+            report.line(format!("Updated task {}: {}", edited.id, edited.description));
+            report
+        }
+
+        Commands::Undo => {
+            let entry = list.undo()?;
+            let mut report = Report::new("undo", json!({ "entry": entry.as_ref().map(entry_json) }));
+            match entry {
+                Some(entry) => {
+                    list.save()?;
+                    report.line(format!("Undid: {}", entry.summary()));
+                }
+                None => report.line("Nothing to undo."),
+            }
+            report
+        }
+
+        Commands::Redo => {
+            let entry = list.redo()?;
+            let mut report = Report::new("redo", json!({ "entry": entry.as_ref().map(entry_json) }));
+            match entry {
+                Some(entry) => {
+                    list.save()?;
+                    report.line(format!("Redid: {}", entry.summary()));
+                }
+                None => report.line("Nothing to redo."),
+            }
+            report
+        }
+
+        Commands::History { limit } => {
+            let journal = list.history()?;
+            let entries: Vec<_> = journal.entries().rev().take(limit).collect();
+            let json_entries: Vec<Value> = entries.iter()
+                .map(|&(entry, applied)| {
+                    let mut json = entry_json(entry);
+                    json["undone"] = Value::Bool(!applied);
+                    json
+                })
+                .collect();
+            let mut report = Report::new("history", json!({ "entries": json_entries })).records("entries");
+            for (entry, applied) in entries {
+                let undone = if applied { "" } else { " (undone)" };
+                report.line(format!("{}  {}{}", entry.time, entry.summary(), undone));
+            }
+            report
+        }
+
+        Commands::Export { format, filter, columns } => {
+            if columns.is_some() && format.delimiter().is_none() {
+                return Err(Error::Validation("--columns only applies to the csv and tsv formats".to_string()));
+            }
+            let filter = parse_filter(filter.as_deref())?;
+            let todos = list.query(|todo| filter.as_ref().is_none_or(|filter| filter.matches(todo)))?;
+            let mut report = Report::new("export", json!({ "format": format.as_str(), "todos": todos })).records("todos");
+            match format {
+                FileFormat::Json => report.line(serde_json::to_string_pretty(&todos)?),
+                FileFormat::Todotxt => todos.iter().for_each(|todo| report.line(todotxt::format_line(todo))),
+                // The calendar's lines end in CRLF; printing adds the final LF
+                FileFormat::Ical => report.line(ical::format(&todos).trim_end_matches('\n')),
+                FileFormat::Csv | FileFormat::Tsv => {
+                    let columns = columns.map_or(Field::ALL.to_vec(), |ColumnList(columns)| columns);
+                    let delimiter = format.delimiter().unwrap_or(',');
+                    report.line(csv::format(&todos, &columns, delimiter).trim_end_matches('\n'));
+                }
+                FileFormat::Markdown => report.line(markdown::format(&todos).trim_end_matches('\n')),
+                FileFormat::Taskwarrior => report.line(taskwarrior::format(&todos).trim_end_matches('\n')),
+            }
+            report
+        }
+
+        Commands::Import { format, input, map, dry_run } => {
+            if !map.is_empty() && format.delimiter().is_none() {
+                return Err(Error::Validation("--map only applies to the csv and tsv formats".to_string()));
+            }
+            let text = match input.filter(|input| input.as_os_str() != "-") {
+                Some(input) => fs::read_to_string(&input).map_err(|e| {
+                    Error::Io(io::Error::new(e.kind(), format!("cannot read {}: {}", input.display(), e)))
+                })?,
+                None => io::read_to_string(io::stdin())?,
+            };
+            let mut ignored = Vec::new();
+            let todos = match format {
+                FileFormat::Json => serde_json::from_str::<Vec<Todo>>(&text)
+                    .map_err(|e| Error::Validation(format!("invalid JSON export: {}", e)))?,
+                FileFormat::Todotxt => todotxt::parse(&text)?,
+                FileFormat::Ical => ical::parse(&text)?,
+                FileFormat::Csv | FileFormat::Tsv => {
+                    let mapping: Vec<_> = map.into_iter().map(|ColumnMapping(column, field)| (column, field)).collect();
+                    let rows = csv::parse(&text, format.delimiter().unwrap_or(','), &mapping, Date::today())?;
+                    if !rows.errors.is_empty() {
+                        let total = rows.errors.len() + rows.todos.len();
+                        return Err(Error::Validation(format!(
+                            "{} of {} rows are invalid, so nothing was imported:\n  {}",
+                            rows.errors.len(),
+                            total,
+                            rows.errors.join("\n  ")
+                        )));
+                    }
+                    ignored = rows.ignored;
+                    rows.todos
+                }
+                // Links to stored tasks are for `sync`; an import adds every item
+                FileFormat::Markdown => markdown::parse(&text)?.into_iter().map(|item| item.todo).collect(),
+                FileFormat::Taskwarrior => taskwarrior::parse(&text)?,
+            };
+
+            // iCalendar and Taskwarrior tasks are matched to stored ones by
+            // UID, so importing a file again updates them instead of adding copies
+            let uid_of = |todo: &Todo| match format {
+                FileFormat::Ical => Some(ical::uid(todo)),
+                FileFormat::Taskwarrior => Some(taskwarrior::uuid(todo)),
+                _ => None,
+            };
+            let mut by_uid: HashMap<String, Todo> = HashMap::new();
+            if matches!(format, FileFormat::Ical | FileFormat::Taskwarrior) {
+                by_uid.extend(list.all()?.into_iter().filter_map(|todo| Some((uid_of(&todo)?, todo))));
+            }
+            // All or nothing: a bad entry fails the command before anything is saved
+            let (mut added, mut updated, mut unchanged) = (Vec::new(), Vec::new(), Vec::new());
+            // Imported tasks name their parents by the IDs they had in the
+            // file; a parent that comes later is linked once it is added
+            let mut new_ids: HashMap<u64, u64> = HashMap::new();
+            let mut later = Vec::new();
+            for todo in todos {
+                let uid = uid_of(&todo);
+                let stored = match uid.as_ref().and_then(|uid| by_uid.get(uid)) {
+                    Some(existing) => {
+                        let merged = match format {
+                            FileFormat::Taskwarrior => taskwarrior::merge(existing, &todo),
+                            _ => ical::merge(existing, &todo),
+                        };
+                        if merged == *existing {
+                            unchanged.push(merged.clone());
+                            merged
+                        } else {
+                            let merged = list.edit(&merged)?;
+                            updated.push(merged.clone());
+                            merged
+                        }
+                    }
+                    None => {
+                        let (file_id, file_parent) = (todo.id, todo.parent);
+                        let parent = file_parent.and_then(|parent| new_ids.get(&parent).copied());
+                        let todo = list.import(Todo { parent, ..todo })?;
+                        new_ids.insert(file_id, todo.id);
+                        if let Some(file_parent) = file_parent.filter(|_| parent.is_none()) {
+                            later.push((added.len(), file_parent));
+                        }
+                        added.push(todo.clone());
+                        todo
+                    }
+                };
+                if let Some(uid) = uid {
+                    by_uid.insert(uid, stored);
+                }
+            }
+            // Parents that aren't in the file are dropped
+            for (i, file_parent) in later {
+                if let Some(&parent) = new_ids.get(&file_parent) {
+                    added[i] = list.edit(&Todo { parent: Some(parent), ..added[i].clone() })?;
+                }
+            }
+            // A dry run makes every change, then drops the list unsaved
+            if !dry_run {
+                list.save()?;
+            }
+
+            let ids = |todos: &[Todo]| todos.iter().map(|todo| todo.id).collect::<Vec<_>>();
+            let count = added.len() + updated.len() + unchanged.len();
+            let mut report = Report::new("import", json!({
+                "todos": added.iter().chain(&updated).chain(&unchanged).collect::<Vec<_>>(),
+                "added": ids(&added),
+                "updated": ids(&updated),
+                "unchanged": ids(&unchanged),
+                "ignored_columns": ignored,
+                "dry_run": dry_run,
+            }))
+            .records("todos");
+            if dry_run {
+                let today = Date::today();
+                for todo in &added {
+                    report.line(format!("Would add {}", todo_line(todo, today, false)));
+                }
+                for todo in &updated {
+                    report.line(format!("Would update {}", todo_line(todo, today, false)));
+                }
+            }
+            if !ignored.is_empty() {
+                report.line(format!("Ignored columns: {}", ignored.join(", ")));
+            }
+            let verb = if dry_run { "Would import" } else { "Imported" };
+            let tasks = if count == 1 { "task" } else { "tasks" };
+            if added.len() == count {
+                report.line(format!("{} {} {}.", verb, count, tasks));
             } else {
-                eprintln!("Error: Task {} doesn't exist", index);
+                report.line(format!(
+                    "{} {} {}: {} added, {} updated, {} unchanged.",
+                    verb,
+                    count,
+                    tasks,
+                    added.len(),
+                    updated.len(),
+                    unchanged.len()
+                ));
             }
+            report
         }
+
+        Commands::Sync { markdown } => {
+            let text = fs::read_to_string(&markdown).map_err(|e| {
+                Error::Io(io::Error::new(e.kind(), format!("cannot read {}: {}", markdown.display(), e)))
+            })?;
+            let modified = fs::metadata(&markdown)?.modified()?;
+            let modified = Timestamp::from_unix(modified.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64));
+            let synced = markdown::sync(&mut list, &text, modified)?;
+            // Save the tasks first: if writing the file fails, the next sync
+            // finds the new items unlinked and would add them again
+            list.save()?;
+            if synced.text != text {
+                store::replace_file(&markdown, &synced.text, false)?;
+            }
+
+            let mut report = Report::new("sync", json!({
+                "file": markdown,
+                "added": synced.added,
+                "updated": synced.updated,
+                "written": synced.written,
+                "missing": synced.missing,
+            }));
+            for todo in &synced.added {
+                report.line(format!("Added task {}: {}", todo.id, todo.description));
+            }
+            for todo in &synced.updated {
+                report.line(format!("Task '{}' is now {}, as in the file.", todo.description, todo.status));
+            }
+            for todo in &synced.written {
+                report.line(format!("Task '{}' is {} in the file now.", todo.description, todo.status));
+            }
+            for id in &synced.missing {
+                report.line(format!("Task {} in the file doesn't exist any more; its item was left alone.", id));
+            }
+            if synced.added.is_empty() && synced.updated.is_empty() && synced.written.is_empty() {
+                report.line(format!("{} is in sync.", markdown.display()));
+            }
+            report
+        }
+
+        Commands::Doctor | Commands::Compact => unreachable!("maintenance commands run before the list is opened"),
+    };
+    Ok(report)
+}
+
+fn main() {
+    // Parse command-line arguments into Cli struct. Usage errors exit with
+    // code 2, reported as JSON too if that is what was asked for.
+    let args: Vec<OsString> = env::args_os().collect();
+    let cli = match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => match requested_format(&args) {
+            OutputFormat::Text => e.exit(),
+            format => {
+                // Just clap's one-line message, without the usage hints
+                let rendered = e.to_string();
+                let message = rendered.lines().next().unwrap_or_default().trim_start_matches("error: ");
+                print_error(format, "usage", message, 2);
+                std::process::exit(2);
+            }
+        },
+    };
+
+    let format = cli.output;
+    if let Err(e) = run(cli).and_then(|report| report.print(format)) {
+        print_error(format, e.kind(), &e.to_string(), e.exit_code());
+        std::process::exit(e.exit_code());
     }
 }
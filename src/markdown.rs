@@ -0,0 +1,283 @@
+//! Reading and writing Markdown task lists, as GitHub renders them:
+//!
+//! ```markdown
+//! - [ ] Plan the offsite @work <!-- id:3 status:todo -->
+//!   - [x] Book the venue +travel @work <!-- id:4 status:done -->
+//!   - [ ] Send the agenda
+//! ```
+//!
+//! The checkbox holds the status, written as `list` prints it: `[ ]`, `[~]`,
+//! `[!]`, `[x]` (or `[X]`) and `[-]`; GitHub only draws the first and
+//! `[x]` as checkboxes. Items nested under an item are its subtasks, and the
+//! text is read like `add` reads a description, so `+tag` and `@project`
+//! words become tags and the project; of several `@` words (GitHub
+//! @mentions, say), the last is the project and the others stay in the
+//! description. Due dates and priorities aren't written. Other lines, including those in code blocks, are left alone.
+//!
+//! The comment at the end links an item to a stored task, for [`sync`]: it
+//! names the task's ID and its status when the file was last written.
+
+use std::fmt::Write as _;
+
+use crate::{Error, Status, Timestamp, Todo, TodoList};
+
+/// A task item read from a Markdown file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// The 0-based line the item is on.
+    pub line: usize,
+    /// The task. Its `id` is the item's 1-based position among the file's
+    /// items, and its `parent` the position of the item it is nested under.
+    pub todo: Todo,
+    /// The stored task the item's comment links it to, if any.
+    pub link: Option<Link>,
+}
+
+/// The `<!-- id:N status:S -->` comment that links an item to a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    /// The task's ID.
+    pub id: u64,
+    /// The task's status when the file was last written, if recorded.
+    pub status: Option<Status>,
+}
+
+/// What [`sync`] did.
+#[derive(Debug, Default)]
+pub struct Synced {
+    /// The file's new contents.
+    pub text: String,
+    /// Tasks added for items that weren't linked to one yet.
+    pub added: Vec<Todo>,
+    /// Tasks whose status was changed to the one in the file.
+    pub updated: Vec<Todo>,
+    /// Tasks whose status was written to the file.
+    pub written: Vec<Todo>,
+    /// IDs of linked tasks that no longer exist; their items are left alone.
+    pub missing: Vec<u64>,
+}
+
+/// Read every task item of a Markdown file. Errors name the line.
+pub fn parse(text: &str) -> Result<Vec<Item>, Error> {
+    let mut items: Vec<Item> = Vec::new();
+    // The list items the next one may be nested in, innermost last, with
+    // their indentation and their position if they are tasks
+    let mut open: Vec<(usize, Option<u64>)> = Vec::new();
+    let mut fence: Option<&str> = None;
+    for (number, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) {
+            fence = Some(marker);
+            continue;
+        }
+        let Some((indent, content)) = list_item(line) else {
+            // Unindented text ends the list
+            if !trimmed.is_empty() && trimmed.len() == line.len() {
+                open.clear();
+            }
+            continue;
+        };
+        while open.last().is_some_and(|&(outer, _)| outer >= indent) {
+            open.pop();
+        }
+        let parent = open.last().and_then(|&(_, position)| position);
+        let Some((status, text)) = task(&line[content..]) else {
+            open.push((indent, None));
+            continue;
+        };
+
+        let (text, link) = split_link(text);
+        let mut todo = item_todo(text)
+            .and_then(|todo| todo.validate().map(|()| todo))
+            .map_err(|e| Error::Validation(format!("line {}: {}", number + 1, e)))?;
+        let position = items.len() as u64 + 1;
+        todo.id = position;
+        todo.status = status;
+        todo.parent = parent;
+        items.push(Item { line: number, todo, link });
+        open.push((indent, Some(position)));
+    }
+    Ok(items)
+}
+
+/// Write todos as a task list, each followed by its subtasks, indented.
+/// Subtasks of tasks that aren't in `todos` are written at the top level.
+pub fn format(todos: &[Todo]) -> String {
+    let mut out = String::new();
+    let top = todos.iter().filter(|todo| todo.parent.is_none_or(|parent| todos.iter().all(|t| t.id != parent)));
+    for todo in top {
+        write_tree(&mut out, todos, todo, 0);
+    }
+    out
+}
+
+/// Reconcile a Markdown file with the list, both ways, and return the
+/// file's new contents. `modified` is when the file was last changed.
+///
+/// Items without a link are added as tasks and linked. For linked items, a
+/// status changed only in the file is applied to the task, and one changed
+/// only in the list is written to the file. If both changed (or the link
+/// records no status), the newer side wins: the task if it was updated
+/// after `modified`, otherwise the file. Nothing else about linked tasks
+/// changes. The list's changes still need saving.
+pub fn sync(list: &mut TodoList, text: &str, modified: Timestamp) -> Result<Synced, Error> {
+    let items = parse(text)?;
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(str::to_string).collect();
+    let mut synced = Synced::default();
+    // The stored task of each item, by position
+    let mut ids: Vec<Option<u64>> = Vec::new();
+
+    for item in items {
+        let in_file = item.todo.status;
+        let task = match item.link {
+            Some(link) => match list.get(link.id) {
+                Ok(task) => {
+                    let file_changed = link.status != Some(in_file);
+                    let list_changed = link.status != Some(task.status);
+                    let status = if in_file == task.status || (list_changed && !file_changed) {
+                        task.status
+                    } else if file_changed && !list_changed {
+                        in_file
+                    } else if task.updated_at.is_some_and(|updated| updated > modified) {
+                        task.status
+                    } else {
+                        in_file
+                    };
+                    if status != task.status {
+                        let task = list.set_status(task.id, status)?;
+                        synced.updated.push(task.clone());
+                        task
+                    } else {
+                        if status != in_file {
+                            synced.written.push(task.clone());
+                        }
+                        task
+                    }
+                }
+                Err(Error::NotFound(_)) => {
+                    synced.missing.push(link.id);
+                    ids.push(None);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            },
+            None => {
+                let mut todo = item.todo.clone();
+                todo.id = 0;
+                todo.parent = item.todo.parent.and_then(|position| ids[position as usize - 1]);
+                let task = list.add_todo(todo)?;
+                synced.added.push(task.clone());
+                task
+            }
+        };
+        ids.push(Some(task.id));
+        let line = &mut lines[item.line];
+        let ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+        *line = relink(line.trim_end_matches(['\r', '\n']), &task) + ending;
+    }
+    synced.text = lines.concat();
+    Ok(synced)
+}
+
+/// The todo an item's text describes, read like `add` reads a description
+/// except that only the last `@` word is the project (the one `format`
+/// writes). Others, such as GitHub @mentions, stay in the description.
+fn item_todo(text: &str) -> Result<Todo, Error> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let marked = |i: usize, prefix: char| words[i].len() > 1 && words[i].starts_with(prefix);
+    let project = (0..words.len()).rev().find(|&i| marked(i, '@'));
+    let except = |skip: &dyn Fn(usize) -> bool| {
+        let kept: Vec<&str> = (0..words.len()).filter(|&i| !skip(i)).map(|i| words[i]).collect();
+        kept.join(" ")
+    };
+    // Tags and the project come from the text without the other `@` words,
+    // and the description from the text without the tags and the project
+    let mut todo = Todo::parse(&except(&|i| marked(i, '@') && Some(i) != project))?;
+    todo.description = except(&|i| marked(i, '+') || Some(i) == project);
+    Ok(todo)
+}
+
+/// Write `todo` and, below it, its subtasks in `todos`.
+fn write_tree(out: &mut String, todos: &[Todo], todo: &Todo, depth: usize) {
+    let _ = write!(out, "{}- {} {}", "  ".repeat(depth), todo.status.marker(), todo.description);
+    for tag in &todo.tags {
+        let _ = write!(out, " +{}", tag);
+    }
+    if let Some(project) = &todo.project {
+        let _ = write!(out, " @{}", project);
+    }
+    let _ = writeln!(out, " {}", link(todo));
+    for subtask in todos.iter().filter(|t| t.parent == Some(todo.id)) {
+        write_tree(out, todos, subtask, depth + 1);
+    }
+}
+
+/// The comment linking an item to `todo`.
+fn link(todo: &Todo) -> String {
+    format!("<!-- id:{} status:{} -->", todo.id, todo.status)
+}
+
+/// An item line with its checkbox and link brought up to date with `todo`.
+fn relink(line: &str, todo: &Todo) -> String {
+    let Some((_, content)) = list_item(line) else {
+        return line.to_string();
+    };
+    let rest = line[content..].trim_start();
+    let start = line.len() - rest.len();
+    // Keep the box as written if it already says the same, e.g. `[X]`
+    let marker = match task(rest) {
+        Some((status, _)) if status == todo.status => &rest[..3],
+        _ => todo.status.marker(),
+    };
+    let text = split_link(rest[3..].trim()).0;
+    let text = if text.is_empty() { String::new() } else { format!("{} ", text) };
+    format!("{}{} {}{}", &line[..start], marker, text, link(todo))
+}
+
+/// If `line` is a list item (`-`, `*`, `+` or `1.`), its indentation, with
+/// tabs counting as 4, and where the content after the bullet starts.
+fn list_item(line: &str) -> Option<(usize, usize)> {
+    let rest = line.trim_start_matches([' ', '\t']);
+    let indent = line[..line.len() - rest.len()].chars().map(|c| if c == '\t' { 4 } else { 1 }).sum();
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let bullet = match digits {
+        0 if rest.starts_with(['-', '*', '+']) => 1,
+        1..=9 if rest[digits..].starts_with(['.', ')']) => digits + 1,
+        _ => return None,
+    };
+    let after = &rest[bullet..];
+    (after.is_empty() || after.starts_with([' ', '\t'])).then_some((indent, line.len() - after.len()))
+}
+
+/// The status and text of an item's content if it starts with a checkbox.
+fn task(content: &str) -> Option<(Status, &str)> {
+    let content = content.trim_start();
+    let marker = content.get(..3)?.replace('X', "x");
+    let status = Status::ALL.into_iter().find(|status| status.marker() == marker)?;
+    let text = &content[3..];
+    (text.is_empty() || text.starts_with([' ', '\t'])).then_some((status, text.trim()))
+}
+
+/// Take a `<!-- id:N status:S -->` link off the end of an item's text.
+fn split_link(text: &str) -> (&str, Option<Link>) {
+    let parsed = text.strip_suffix("-->").and_then(|head| {
+        let start = head.rfind("<!--")?;
+        let mut words = head[start + 4..].split_whitespace();
+        let id = words.next()?.strip_prefix("id:")?.parse().ok()?;
+        let status = match words.next() {
+            Some(word) => Some(word.strip_prefix("status:")?.parse().ok()?),
+            None => None,
+        };
+        words.next().is_none().then_some((start, Link { id, status }))
+    });
+    match parsed {
+        Some((start, link)) => (text[..start].trim_end(), Some(link)),
+        None => (text, None),
+    }
+}
@@ -0,0 +1,242 @@
+//! The data model: a single todo item.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Date, Error, Timestamp};
+
+/// Data model representing a single todo item.
+/// Derives Serialize/Deserialize for JSON persistence.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Todo {
+    /// Stable identifier assigned when the todo is added.
+    /// IDs are never reused, so removing a task doesn't renumber the others.
+    pub id: u64,
+    /// What needs doing.
+    pub description: String,
+    /// Where the task is in its workflow.
+    pub status: Status,
+    /// The day the task should be finished by, if any.
+    pub due: Option<Date>,
+    /// How important the task is, if that has been decided.
+    pub priority: Option<Priority>,
+    /// Free-form labels, written `+tag` on the command line.
+    pub tags: Vec<String>,
+    /// The stream of work the task belongs to, written `@project`.
+    pub project: Option<String>,
+    /// The ID of the task this is a subtask of, if any.
+    pub parent: Option<u64>,
+    /// When the task was added; `None` for tasks from before this was recorded.
+    pub created_at: Option<Timestamp>,
+    /// When the task last changed in any way.
+    pub updated_at: Option<Timestamp>,
+    /// When the task was marked as done; `None` while it isn't done.
+    pub completed_at: Option<Timestamp>,
+    /// Fields brought in by imports that no other field can hold (e.g. todo.txt
+    /// `key:value` extensions), kept so exporting again doesn't lose them.
+    pub extra: BTreeMap<String, Value>,
+}
+
+/// The workflow state of a todo.
+///
+/// `Todo`, `InProgress` and `Blocked` are *open* (still needs work);
+/// `Done` and `Cancelled` are *closed*.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    /// Not started yet.
+    #[default]
+    Todo,
+    /// Being worked on.
+    InProgress,
+    /// Waiting on something else.
+    Blocked,
+    /// Finished.
+    Done,
+    /// Won't be done.
+    Cancelled,
+}
+
+impl Status {
+    /// Every status, in workflow order.
+    pub const ALL: [Status; 5] = [Status::Todo, Status::InProgress, Status::Blocked, Status::Done, Status::Cancelled];
+
+    /// True while the task still needs work.
+    pub fn is_open(self) -> bool {
+        !matches!(self, Status::Done | Status::Cancelled)
+    }
+
+    /// The checkbox `list` prints for this status.
+    pub fn marker(self) -> &'static str {
+        match self {
+            Status::Todo => "[ ]",
+            Status::InProgress => "[~]",
+            Status::Blocked => "[!]",
+            Status::Done => "[x]",
+            Status::Cancelled => "[-]",
+        }
+    }
+
+    /// The name used on the command line and in files, e.g. `in-progress`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Todo => "todo",
+            Status::InProgress => "in-progress",
+            Status::Blocked => "blocked",
+            Status::Done => "done",
+            Status::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Status {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Status, Error> {
+        Status::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Status::ALL.iter().map(|status| status.as_str()).collect();
+                Error::Validation(format!("unknown status `{}` (expected one of: {})", s, names.join(", ")))
+            })
+    }
+}
+
+/// How important a todo is. Orders from most to least important, so sorting
+/// ascending puts `High` first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Priority {
+    /// Do this first.
+    #[serde(rename = "H")]
+    High,
+    /// The normal case.
+    #[serde(rename = "M")]
+    Medium,
+    /// Whenever there's time.
+    #[serde(rename = "L")]
+    Low,
+}
+
+impl Priority {
+    /// Every priority, from most to least important.
+    pub const ALL: [Priority; 3] = [Priority::High, Priority::Medium, Priority::Low];
+
+    /// The one-letter name used on the command line and in files.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Priority::High => "H",
+            Priority::Medium => "M",
+            Priority::Low => "L",
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Accepts `H`/`M`/`L` or `high`/`medium`/`low`, in any case.
+impl FromStr for Priority {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Priority, Error> {
+        match s.to_lowercase().as_str() {
+            "h" | "high" => Ok(Priority::High),
+            "m" | "medium" => Ok(Priority::Medium),
+            "l" | "low" => Ok(Priority::Low),
+            _ => Err(Error::Validation(format!("unknown priority `{}` (expected H, M or L)", s))),
+        }
+    }
+}
+
+impl Todo {
+    /// A new todo that hasn't been started. Its ID is assigned when it is stored.
+    pub fn new(description: impl Into<String>) -> Todo {
+        Todo {
+            id: 0,
+            description: description.into(),
+            status: Status::Todo,
+            due: None,
+            priority: None,
+            tags: Vec::new(),
+            project: None,
+            parent: None,
+            created_at: None,
+            updated_at: None,
+            completed_at: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    /// A new todo from text that may contain `+tag` and `@project` tokens,
+    /// e.g. `"Fix login +bug +urgent @website"`. The tokens are taken out of
+    /// the description and stored in `tags` and `project`.
+    pub fn parse(text: &str) -> Result<Todo, Error> {
+        let mut todo = Todo::new("");
+        let mut words = Vec::new();
+        for word in text.split_whitespace() {
+            if let Some(tag) = word.strip_prefix('+').filter(|tag| !tag.is_empty()) {
+                if !todo.tags.iter().any(|t| t == tag) {
+                    todo.tags.push(tag.to_string());
+                }
+            } else if let Some(project) = word.strip_prefix('@').filter(|project| !project.is_empty()) {
+                if todo.project.as_deref().is_some_and(|p| p != project) {
+                    return Err(Error::Validation(format!(
+                        "a task can only belong to one project, not both @{} and @{}",
+                        todo.project.unwrap_or_default(),
+                        project
+                    )));
+                }
+                todo.project = Some(project.to_string());
+            } else {
+                words.push(word);
+            }
+        }
+        todo.description = words.join(" ");
+        Ok(todo)
+    }
+
+    /// True if the task is still open and its due date has passed.
+    pub fn is_overdue(&self, today: Date) -> bool {
+        self.status.is_open() && self.due.is_some_and(|due| due < today)
+    }
+
+    /// Check that the todo can be stored: the description must have some
+    /// text and fit on one line, since `list` prints one task per line.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.description.trim().is_empty() {
+            return Err(Error::Validation("the description must not be empty".to_string()));
+        }
+        if self.description.contains(['\n', '\r']) {
+            return Err(Error::Validation("the description must be a single line".to_string()));
+        }
+        // Tags and projects are single words so they can be written inline
+        for tag in &self.tags {
+            check_name("tag", tag)?;
+        }
+        if let Some(project) = &self.project {
+            check_name("project", project)?;
+        }
+        Ok(())
+    }
+}
+
+/// A tag or project name must be one non-empty word.
+fn check_name(kind: &str, name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(Error::Validation(format!("invalid {} `{}` (must be a single word)", kind, name)));
+    }
+    Ok(())
+}
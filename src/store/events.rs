@@ -0,0 +1,397 @@
+//! Append-only event-log backend.
+//!
+//! Every change is one JSON line appended to the log, recording who made it
+//! and when, so the file doubles as an audit trail. Opening the store folds
+//! the log onto the last snapshot; a truncated last line (from a crash
+//! mid-append) is skipped, and a whole one that lost its newline gets it
+//! back before anything is appended. Compaction writes a new snapshot and
+//! moves the folded events to an archive file.
+//!
+//! Logs of two branches merged by keeping both sides' lines load as well: a
+//! todo that both added under the same ID is renumbered (see
+//! [`Event::Renumbered`]).
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::json::{migrate_todos, SCHEMA_VERSION};
+use super::lock::FileLock;
+use super::{replace_file, sibling_path, TodoStore};
+use crate::{Error, Status, Timestamp, Todo};
+
+/// Once the log holds this many events, `commit` compacts it.
+const COMPACT_AFTER: usize = 500;
+
+/// Something that happened to the list. Each line of the log is one event,
+/// wrapped in a `Record`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A todo was added (or put back by undo), with its ID.
+    Added {
+        /// The todo as it was added.
+        todo: Todo,
+    },
+    /// A todo was marked as done, and nothing else about it changed. The
+    /// record's timestamp is when it was completed.
+    Completed {
+        /// The todo's ID.
+        id: u64,
+    },
+    /// A todo was removed.
+    Removed {
+        /// The todo's ID.
+        id: u64,
+    },
+    /// Anything else changed about a todo; holds the whole new version.
+    Edited {
+        /// The todo after the change.
+        todo: Todo,
+    },
+    /// Branches that both added a todo under the same ID were merged, and
+    /// the later one was given a new ID: from its `added` event up to this
+    /// note, `from` meant `to`. Afterwards IDs mean themselves again.
+    Renumbered {
+        /// The ID both branches used.
+        from: u64,
+        /// The ID the later todo has now.
+        to: u64,
+    },
+}
+
+/// One line of the log: an event, and who recorded it when.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Record {
+    /// Increases with every event, so a snapshot can tell which ones it covers.
+    seq: u64,
+    /// Schema version of the todo inside the event, if it holds one.
+    v: u64,
+    ts: Timestamp,
+    user: String,
+    #[serde(flatten)]
+    event: Event,
+}
+
+/// The state of the list folded from every event up to `last_seq`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    version: u64,
+    next_id: u64,
+    last_seq: u64,
+    todos: Vec<Todo>,
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Snapshot { version: SCHEMA_VERSION, next_id: 1, last_seq: 0, todos: Vec::new() }
+    }
+}
+
+/// An append-only backend: every change is appended to a JSON-lines event
+/// log, and the current list is rebuilt by folding the log onto the last
+/// snapshot. Compaction writes a new snapshot and moves the folded events
+/// from the log to `<log>.archive`, so the full audit trail is kept.
+pub struct EventStore {
+    path: PathBuf,
+    state: Snapshot,
+    /// Events in the log file that the snapshot doesn't cover.
+    logged: usize,
+    /// Events not written yet.
+    pending: Vec<Record>,
+    /// IDs reused by a merged branch, and the new IDs they stand for until
+    /// a `Renumbered` note.
+    renumbered: BTreeMap<u64, u64>,
+    _lock: FileLock,
+}
+
+impl EventStore {
+    /// Lock the event log at `path` and rebuild the list from it.
+    pub fn open(path: &Path, exclusive: bool) -> Result<EventStore, Error> {
+        let lock = FileLock::acquire(path, exclusive)?;
+        let context = |e: Error| match e {
+            Error::Parse(message) => Error::Parse(format!("cannot load {}: {}", path.display(), message)),
+            e => e,
+        };
+        let mut store = EventStore {
+            path: path.to_path_buf(),
+            state: load_snapshot(&snapshot_path(path)).map_err(context)?,
+            logged: 0,
+            pending: Vec::new(),
+            renumbered: BTreeMap::new(),
+            _lock: lock,
+        };
+        if path.exists() {
+            // Events the snapshot already covers are left over from a
+            // compaction that was interrupted. Everything else is applied in
+            // file order, which after a merge can repeat sequence numbers.
+            let covered = store.state.last_seq;
+            let log = fs::read_to_string(path)?;
+            let mut complete = log.is_empty() || log.ends_with('\n');
+            let lines: Vec<&str> = log.lines().collect();
+            for (number, line) in lines.iter().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+                let at_line = |e: Error| context(Error::Parse(format!("line {}: {}", number + 1, e)));
+                let record = match parse_record(line) {
+                    Ok(record) => record,
+                    // A crash in the middle of an append leaves a partial last
+                    // line; that commit never finished, so it is ignored (and
+                    // cut off before anything else is appended)
+                    Err(_) if !complete && number + 1 == lines.len() => {
+                        if exclusive {
+                            let file = OpenOptions::new().write(true).open(path)?;
+                            file.set_len((log.len() - line.len()) as u64)?;
+                            file.sync_all()?;
+                        }
+                        complete = true;
+                        break;
+                    }
+                    Err(e) => return Err(at_line(e)),
+                };
+                if record.seq > covered {
+                    store.apply(&record).map_err(at_line)?;
+                }
+                store.state.last_seq = store.state.last_seq.max(record.seq);
+                store.logged += 1;
+            }
+            // A whole last line can still lack its newline (an editor may have
+            // dropped it); the next append must not be glued onto it
+            if exclusive && !complete {
+                let mut file = OpenOptions::new().append(true).open(path)?;
+                file.write_all(b"\n")?;
+                file.sync_all()?;
+            }
+        }
+        // Note every renumbering from a merge, so that events appended from
+        // here on name those todos by their new IDs
+        if exclusive && !store.renumbered.is_empty() {
+            let renumbered = std::mem::take(&mut store.renumbered);
+            for (from, to) in renumbered {
+                store.record(Timestamp::now(), Event::Renumbered { from, to })?;
+            }
+            store.commit_events()?;
+        }
+        Ok(store)
+    }
+
+    /// Fold the whole log into a new snapshot, archive the events and start
+    /// an empty log. Returns how many events were compacted.
+    pub fn compact(&mut self) -> Result<usize, Error> {
+        self.commit_events()?;
+        let snapshot = serde_json::to_string_pretty(&self.state)?;
+        replace_file(&snapshot_path(&self.path), &snapshot, false)?;
+
+        // The snapshot now covers every event (by `last_seq`), so a crash
+        // from here on can only leave events behind that loading skips
+        if self.path.exists() {
+            let log = fs::read_to_string(&self.path)?;
+            let mut archive = OpenOptions::new().create(true).append(true).open(sibling_path(&self.path, "archive"))?;
+            archive.write_all(log.as_bytes())?;
+            archive.sync_all()?;
+        }
+        replace_file(&self.path, "", false)?;
+        Ok(std::mem::take(&mut self.logged))
+    }
+
+    fn position(&self, id: u64) -> Result<usize, Error> {
+        self.state.todos.iter()
+            .position(|todo| todo.id == id)
+            .ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+
+    /// Update the state for an event read from the log or just recorded.
+    fn apply(&mut self, record: &Record) -> Result<(), Error> {
+        let mut event = record.event.clone();
+        let renumber = |id: &mut u64| {
+            if let Some(&to) = self.renumbered.get(id) {
+                *id = to;
+            }
+        };
+        match &mut event {
+            Event::Added { todo } | Event::Edited { todo } => {
+                renumber(&mut todo.id);
+                if let Some(parent) = &mut todo.parent {
+                    renumber(parent);
+                }
+            }
+            Event::Completed { id } | Event::Removed { id } => renumber(id),
+            Event::Renumbered { .. } => {}
+        }
+
+        match &mut event {
+            Event::Added { todo } => {
+                let position = match self.state.todos.binary_search_by_key(&todo.id, |t| t.id) {
+                    // Two merged branches both added a todo under this ID:
+                    // the later one gets the next free ID
+                    Ok(_) => {
+                        let to = self.state.next_id;
+                        self.renumbered.insert(todo.id, to);
+                        todo.id = to;
+                        self.state.todos.len()
+                    }
+                    Err(position) => position,
+                };
+                self.state.todos.insert(position, todo.clone());
+                self.state.next_id = self.state.next_id.max(todo.id + 1);
+            }
+            Event::Completed { id } => {
+                let position = self.position(*id)?;
+                let todo = &mut self.state.todos[position];
+                todo.status = Status::Done;
+                todo.updated_at = Some(record.ts);
+                todo.completed_at = Some(record.ts);
+            }
+            Event::Removed { id } => {
+                let position = self.position(*id)?;
+                self.state.todos.remove(position);
+            }
+            Event::Edited { todo } => {
+                let position = self.position(todo.id)?;
+                self.state.todos[position] = todo.clone();
+            }
+            Event::Renumbered { from, .. } => {
+                self.renumbered.remove(from);
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a new event that happened at `ts` and queue it for the log.
+    fn record(&mut self, ts: Timestamp, event: Event) -> Result<(), Error> {
+        let record = Record {
+            seq: self.state.last_seq + 1,
+            v: SCHEMA_VERSION,
+            ts,
+            user: current_user(),
+            event,
+        };
+        self.apply(&record)?;
+        self.state.last_seq = record.seq;
+        self.pending.push(record);
+        Ok(())
+    }
+
+    /// Append the queued events to the log and fsync it.
+    fn commit_events(&mut self) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut lines = String::new();
+        for record in &self.pending {
+            lines.push_str(&serde_json::to_string(record)?);
+            lines.push('\n');
+        }
+        let mut log = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        log.write_all(lines.as_bytes())?;
+        log.sync_all()?;
+        self.logged += self.pending.len();
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl TodoStore for EventStore {
+    fn query(&self, filter: &dyn Fn(&Todo) -> bool) -> Result<Vec<Todo>, Error> {
+        Ok(self.state.todos.iter().filter(|todo| filter(todo)).cloned().collect())
+    }
+
+    fn insert(&mut self, mut todo: Todo) -> Result<Todo, Error> {
+        todo.id = self.state.next_id;
+        self.record(Timestamp::now(), Event::Added { todo: todo.clone() })?;
+        Ok(todo)
+    }
+
+    fn update(&mut self, todo: &Todo) -> Result<(), Error> {
+        // A plain completion is logged compactly, with its completion time
+        // as the record's timestamp
+        let current = &self.state.todos[self.position(todo.id)?];
+        if let Some(at) = todo.completed_at.filter(|_| current.status != Status::Done) {
+            let completed = Todo { status: Status::Done, updated_at: Some(at), completed_at: Some(at), ..current.clone() };
+            if *todo == completed {
+                return self.record(at, Event::Completed { id: todo.id });
+            }
+        }
+        self.record(Timestamp::now(), Event::Edited { todo: todo.clone() })
+    }
+
+    fn delete(&mut self, id: u64) -> Result<Todo, Error> {
+        let todo = self.state.todos[self.position(id)?].clone();
+        self.record(Timestamp::now(), Event::Removed { id })?;
+        Ok(todo)
+    }
+
+    fn restore(&mut self, todo: &Todo) -> Result<(), Error> {
+        if self.position(todo.id).is_ok() {
+            return Err(Error::Validation(format!("task {} already exists", todo.id)));
+        }
+        self.record(Timestamp::now(), Event::Added { todo: todo.clone() })
+    }
+
+    fn commit(&mut self) -> Result<(), Error> {
+        self.commit_events()?;
+        if self.logged >= COMPACT_AFTER {
+            self.compact()?;
+        }
+        Ok(())
+    }
+}
+
+/// `todos.events.jsonl` -> `todos.events.jsonl.snapshot`
+fn snapshot_path(log_path: &Path) -> PathBuf {
+    sibling_path(log_path, "snapshot")
+}
+
+fn load_snapshot(path: &Path) -> Result<Snapshot, Error> {
+    if !path.exists() {
+        return Ok(Snapshot::default());
+    }
+    let mut doc: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let version = doc.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > SCHEMA_VERSION {
+        return Err(newer_version(version));
+    }
+    if version < SCHEMA_VERSION {
+        // A snapshot without a list of todos is damaged, not empty
+        let todos = doc.get_mut("todos").map_or(Value::Null, Value::take);
+        let todos = serde_json::from_value(todos)
+            .map_err(|e| Error::Parse(format!("invalid `todos` in the snapshot: {}", e)))?;
+        doc["todos"] = serde_json::to_value(migrate_todos(version, todos)?)?;
+        doc["version"] = json!(SCHEMA_VERSION);
+    }
+    Ok(serde_json::from_value(doc)?)
+}
+
+/// Parse one line of the log, upgrading the todo inside if it was written
+/// with an older schema.
+fn parse_record(line: &str) -> Result<Record, Error> {
+    let mut record: Value = serde_json::from_str(line)?;
+    let version = record.get("v").and_then(Value::as_u64).unwrap_or(0);
+    if version > SCHEMA_VERSION {
+        return Err(newer_version(version));
+    }
+    if version < SCHEMA_VERSION {
+        if let Some(todo) = record.get_mut("todo") {
+            let migrated = migrate_todos(version, vec![todo.take()])?;
+            *todo = serde_json::to_value(&migrated[0])?;
+        }
+        record["v"] = json!(SCHEMA_VERSION);
+    }
+    Ok(serde_json::from_value(record)?)
+}
+
+fn newer_version(version: u64) -> Error {
+    Error::Parse(format!(
+        "it uses schema version {}, but this build only understands up to version {}; please upgrade cli-todo-rust",
+        version, SCHEMA_VERSION
+    ))
+}
+
+/// Who to credit an event to: the login name, if the environment has one.
+fn current_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
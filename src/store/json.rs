@@ -0,0 +1,448 @@
+//! The default backend: the whole list in one JSON file.
+//!
+//! The file is a versioned envelope around the todos. Files written by
+//! older versions are upgraded in memory by the migrations below, which the
+//! other backends reuse for their own old data. `doctor` salvages what it
+//! can from a file that no longer parses.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::lock::FileLock;
+use super::{replace_file, sibling_path, TodoStore};
+use crate::{Error, Todo};
+
+/// Version of the on-disk format written by this build.
+/// Bump it together with a new entry in `MIGRATIONS` whenever `Todo` or
+/// `TodoFile` changes shape.
+pub(crate) const SCHEMA_VERSION: u64 = 9;
+
+/// `MIGRATIONS[n]` upgrades a version-n document to version n+1.
+/// Loading runs every step from the file's version up to `SCHEMA_VERSION`.
+const MIGRATIONS: [fn(Value) -> Result<Value, String>; SCHEMA_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+    migrate_v5_to_v6,
+    migrate_v6_to_v7,
+    migrate_v7_to_v8,
+    migrate_v8_to_v9,
+];
+
+/// Everything persisted in the JSON file: the todos plus the ID counter,
+/// wrapped in an envelope that records the schema version.
+/// Keeping `next_id` on disk is what guarantees IDs are never handed out twice,
+/// even after the task with the highest ID has been removed.
+#[derive(Debug, Serialize, Deserialize)]
+struct TodoFile {
+    version: u64,
+    next_id: u64,
+    todos: Vec<Todo>,
+}
+
+impl Default for TodoFile {
+    fn default() -> Self {
+        TodoFile { version: SCHEMA_VERSION, next_id: 1, todos: Vec::new() }
+    }
+}
+
+/// The default backend: the whole list lives in one pretty-printed JSON file
+/// that is read on open and rewritten atomically on commit.
+pub struct JsonStore {
+    path: PathBuf,
+    data: TodoFile,
+    dirty: bool,
+    _lock: FileLock,
+}
+
+impl JsonStore {
+    /// Lock the todo file at `path` and load it, creating it if needed.
+    pub fn open(path: &Path, exclusive: bool) -> Result<JsonStore, Error> {
+        let lock = FileLock::acquire(path, exclusive)?;
+        // Any load failure is fatal: carrying on with an empty list would make
+        // the next save overwrite everything the user had.
+        let data = load_data(path).map_err(|e| match e {
+            Error::Parse(message) => Error::Parse(format!(
+                "cannot load {}: {}\nIf the file is damaged, run `cli-todo-rust doctor` to recover the intact entries.",
+                path.display(),
+                message
+            )),
+            Error::Io(e) => Error::Io(std::io::Error::new(e.kind(), format!("cannot load {}: {}", path.display(), e))),
+            e => e,
+        })?;
+        Ok(JsonStore { path: path.to_path_buf(), data, dirty: false, _lock: lock })
+    }
+
+    fn position(&self, id: u64) -> Result<usize, Error> {
+        self.data.todos.iter()
+            .position(|todo| todo.id == id)
+            .ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+}
+
+impl TodoStore for JsonStore {
+    fn query(&self, filter: &dyn Fn(&Todo) -> bool) -> Result<Vec<Todo>, Error> {
+        Ok(self.data.todos.iter().filter(|todo| filter(todo)).cloned().collect())
+    }
+
+    fn insert(&mut self, mut todo: Todo) -> Result<Todo, Error> {
+        todo.id = self.data.next_id;
+        self.data.next_id += 1;
+        self.data.todos.push(todo.clone());
+        self.dirty = true;
+        Ok(todo)
+    }
+
+    fn update(&mut self, todo: &Todo) -> Result<(), Error> {
+        let position = self.position(todo.id)?;
+        self.data.todos[position] = todo.clone();
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn delete(&mut self, id: u64) -> Result<Todo, Error> {
+        let position = self.position(id)?;
+        self.dirty = true;
+        Ok(self.data.todos.remove(position))
+    }
+
+    fn restore(&mut self, todo: &Todo) -> Result<(), Error> {
+        // Keep the list in ID order, which is the order the todos were added in
+        let position = match self.data.todos.binary_search_by_key(&todo.id, |t| t.id) {
+            Ok(_) => return Err(Error::Validation(format!("task {} already exists", todo.id))),
+            Err(position) => position,
+        };
+        self.data.todos.insert(position, todo.clone());
+        self.data.next_id = self.data.next_id.max(todo.id + 1);
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), Error> {
+        if self.dirty {
+            save_todos(&self.path, &self.data)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+/// Work out which schema version a raw document was written with.
+///
+/// - version 0: a bare array of `{description, completed}` (the original format)
+/// - version 1: `{next_id, todos}` with IDs, but no version field yet
+/// - version 2+: `{version, next_id, todos}`; version 3 replaced each todo's
+///   `completed` flag with a `status`, version 4 added an optional `due` date,
+///   version 5 an optional `priority`, version 6 `tags` and a `project`,
+///   version 7 `created_at`, `updated_at` and `completed_at` timestamps,
+///   version 8 an `extra` object for imported fields, and version 9 an
+///   optional `parent` for subtasks
+fn schema_version(doc: &Value) -> Result<u64, String> {
+    match doc {
+        Value::Array(_) => Ok(0),
+        Value::Object(fields) => match fields.get("version") {
+            None => Ok(1),
+            Some(version) => version.as_u64().ok_or_else(|| format!("invalid schema version {}", version)),
+        },
+        _ => Err("expected a JSON object or array".to_string()),
+    }
+}
+
+/// v0 -> v1: number the todos in list order and add the ID counter.
+fn migrate_v0_to_v1(doc: Value) -> Result<Value, String> {
+    let Value::Array(mut todos) = doc else {
+        return Err("expected a JSON array".to_string());
+    };
+    for (i, todo) in todos.iter_mut().enumerate() {
+        let todo = todo.as_object_mut().ok_or("expected every todo to be an object")?;
+        todo.insert("id".to_string(), json!(i + 1));
+    }
+    Ok(json!({ "next_id": todos.len() + 1, "todos": todos }))
+}
+
+/// v1 -> v2: the envelope only gains the `version` field, which `migrate` sets.
+fn migrate_v1_to_v2(doc: Value) -> Result<Value, String> {
+    Ok(doc)
+}
+
+/// v2 -> v3: `completed: bool` becomes `status: "done"` or `"todo"`.
+fn migrate_v2_to_v3(mut doc: Value) -> Result<Value, String> {
+    for todo in todos_mut(&mut doc)? {
+        let completed = todo.remove("completed").and_then(|c| c.as_bool()).unwrap_or(false);
+        todo.insert("status".to_string(), json!(if completed { "done" } else { "todo" }));
+    }
+    Ok(doc)
+}
+
+/// v3 -> v4: todos gain an optional `due` date; existing ones have none.
+fn migrate_v3_to_v4(mut doc: Value) -> Result<Value, String> {
+    for todo in todos_mut(&mut doc)? {
+        todo.insert("due".to_string(), Value::Null);
+    }
+    Ok(doc)
+}
+
+/// v4 -> v5: todos gain an optional `priority`; existing ones have none.
+fn migrate_v4_to_v5(mut doc: Value) -> Result<Value, String> {
+    for todo in todos_mut(&mut doc)? {
+        todo.insert("priority".to_string(), Value::Null);
+    }
+    Ok(doc)
+}
+
+/// v5 -> v6: todos gain `tags` (none yet) and an optional `project`.
+fn migrate_v5_to_v6(mut doc: Value) -> Result<Value, String> {
+    for todo in todos_mut(&mut doc)? {
+        todo.insert("tags".to_string(), json!([]));
+        todo.insert("project".to_string(), Value::Null);
+    }
+    Ok(doc)
+}
+
+/// v6 -> v7: todos gain `created_at`, `updated_at` and `completed_at`.
+/// When existing todos were created or finished is unknown, so all three
+/// start out empty.
+fn migrate_v6_to_v7(mut doc: Value) -> Result<Value, String> {
+    for todo in todos_mut(&mut doc)? {
+        for field in ["created_at", "updated_at", "completed_at"] {
+            todo.insert(field.to_string(), Value::Null);
+        }
+    }
+    Ok(doc)
+}
+
+/// v7 -> v8: todos gain `extra`, for imported fields with no other home.
+fn migrate_v7_to_v8(mut doc: Value) -> Result<Value, String> {
+    for todo in todos_mut(&mut doc)? {
+        todo.insert("extra".to_string(), json!({}));
+    }
+    Ok(doc)
+}
+
+/// v8 -> v9: todos gain `parent`; existing todos are all top-level tasks.
+fn migrate_v8_to_v9(mut doc: Value) -> Result<Value, String> {
+    for todo in todos_mut(&mut doc)? {
+        todo.insert("parent".to_string(), Value::Null);
+    }
+    Ok(doc)
+}
+
+/// The todo objects inside a version 1+ document, for migrations to edit.
+fn todos_mut(doc: &mut Value) -> Result<impl Iterator<Item = &mut Map<String, Value>>, String> {
+    let todos = doc.get_mut("todos").and_then(Value::as_array_mut).ok_or("expected a `todos` array")?;
+    Ok(todos.iter_mut().filter_map(Value::as_object_mut))
+}
+
+/// Upgrade a raw document of any supported version to `SCHEMA_VERSION`.
+/// Files from a newer build are refused rather than guessed at, since saving
+/// them again would silently drop whatever the newer version added.
+fn migrate(mut doc: Value) -> Result<Value, String> {
+    let version = schema_version(&doc)?;
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "the file uses schema version {}, but this build only understands up to version {}; please upgrade cli-todo-rust",
+            version, SCHEMA_VERSION
+        ));
+    }
+    for (from, step) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        doc = step(doc)?;
+        if let Some(fields) = doc.as_object_mut() {
+            fields.insert("version".to_string(), json!(from + 1));
+        }
+    }
+    Ok(doc)
+}
+
+/// Upgrade a batch of raw todo objects written with schema `version`.
+///
+/// Migrations work on whole documents, so the todos are wrapped in the
+/// envelope of their version, migrated, and unwrapped again. This lets other
+/// places that hold loose todos (salvaged entries, SQLite rows) share the
+/// same upgrade path as the JSON file.
+pub(crate) fn migrate_todos(version: u64, todos: Vec<Value>) -> Result<Vec<Todo>, Error> {
+    let mut doc = match version {
+        0 => json!(todos),
+        _ => json!({ "next_id": 0, "todos": todos }),
+    };
+    if version >= 2 {
+        doc["version"] = json!(version);
+    }
+    let file: TodoFile = serde_json::from_value(migrate(doc).map_err(Error::Parse)?)?;
+    Ok(file.todos)
+}
+
+/// Load todos from JSON file, creating an empty file if none exists.
+/// 
+/// Returns a Result containing either:
+/// - Ok(TodoFile): Successfully loaded todos from file
+/// - Err: File system or deserialization error
+///
+/// Files written by older versions are run through the migrations in memory;
+/// they are written back in the current format on the next save.
+fn load_data(file_path: &Path) -> Result<TodoFile, Error> {
+    let list = if file_path.exists() {
+        // File exists - read and deserialize
+        let data = fs::read_to_string(file_path)?;
+        let doc = migrate(serde_json::from_str(&data)?).map_err(Error::Parse)?;
+        serde_json::from_value(doc)?
+    } else {
+        // File doesn't exist - create its directory and an empty JSON file
+        if let Some(folder) = file_path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        let empty = TodoFile::default();
+        save_todos(file_path, &empty)?;
+        empty
+    };
+
+    Ok(list)
+}
+
+/// Save todos to JSON file with pretty formatting.
+/// 
+/// Uses serde_json::to_string_pretty for human-readable output.
+/// Takes a reference to avoid taking ownership of the todo list.
+///
+/// The write is crash-safe (see `replace_file`), and the previous version is
+/// kept as `<file>.bak` so a bad write can be undone by hand.
+fn save_todos(file_path: &Path, list: &TodoFile) -> Result<(), Error> {
+    // Serialize with indentation for readability
+    let json = serde_json::to_string_pretty(list)?;
+    replace_file(file_path, &json, true)
+}
+
+/// Pull every well-formed todo object out of a damaged JSON document.
+///
+/// This is a tiny brace matcher rather than a real parser: it tracks string
+/// literals (so braces inside descriptions don't count) and keeps each `{...}`
+/// that sits directly inside the todo array - nesting depth 0 for the bare
+/// array format, 1 inside the envelope. Objects that don't parse, including
+/// anything cut off mid-way, are skipped. Each survivor is then run through
+/// the normal migrations on its own, so entries of any schema version can be
+/// recovered and one bad entry can't sink the rest.
+fn salvage_todos(data: &str, version: u64) -> Vec<Todo> {
+    let todo_depth = if version == 0 { 0 } else { 1 };
+    let mut todos = Vec::new();
+    let mut starts = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in data.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => starts.push(i),
+            '}' => {
+                let Some(start) = starts.pop() else { continue };
+                if starts.len() != todo_depth {
+                    continue;
+                }
+                let Ok(todo) = serde_json::from_str::<Value>(&data[start..=i]) else { continue };
+                todos.extend(migrate_todos(version, vec![todo]).unwrap_or_default());
+            }
+            _ => {}
+        }
+    }
+    todos
+}
+
+/// Find a top-level `"key": N` number in a damaged document, if it survived.
+fn salvage_number(data: &str, key: &str) -> Option<u64> {
+    let quoted = format!("\"{}\"", key);
+    let rest = &data[data.find(&quoted)? + quoted.len()..];
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Check the todo file and, if it can't be loaded, rebuild it from whatever
+/// entries are still intact. The damaged original is never deleted: it is
+/// moved aside to `<file>.corrupt-<unix time>` so nothing is lost for good.
+///
+/// Returns a human-readable summary of what was done.
+pub fn repair_file(file_path: &Path) -> Result<String, Error> {
+    let _lock = FileLock::acquire(file_path, true)?;
+    if !file_path.exists() {
+        return Ok(format!("No todo file at {} - nothing to check.", file_path.display()));
+    }
+    let load_error = match load_data(file_path) {
+        Ok(list) => {
+            return Ok(format!("{} is healthy ({} tasks).", file_path.display(), list.todos.len()));
+        }
+        Err(e) => e,
+    };
+
+    let data = String::from_utf8_lossy(&fs::read(file_path)?).into_owned();
+    let version = if data.trim_start().starts_with('[') {
+        0
+    } else {
+        salvage_number(&data, "version").unwrap_or(1)
+    };
+    if version > SCHEMA_VERSION {
+        return Err(Error::Parse(format!(
+            "{} was written by a newer version (schema {}); not touching it",
+            file_path.display(),
+            version
+        )));
+    }
+    let mut todos = salvage_todos(&data, version);
+    let backup = sibling_path(file_path, "bak");
+    let previous = if backup.exists() { load_data(&backup).ok() } else { None };
+
+    // IDs must never be reused, so continue after the highest counter we can
+    // still find: the damaged file's own, the backup's, or the recovered IDs
+    let mut next_id = todos.iter().map(|todo| todo.id + 1)
+        .chain(salvage_number(&data, "next_id"))
+        .chain(previous.as_ref().map(|list| list.next_id))
+        .max()
+        .unwrap_or(1);
+
+    // Legacy entries are all numbered from 1 and damaged files may contain
+    // duplicates: keep the first of each ID and give everything else a fresh one
+    let mut seen = std::collections::HashSet::new();
+    for todo in &mut todos {
+        if todo.id == 0 || !seen.insert(todo.id) {
+            todo.id = next_id;
+            next_id += 1;
+        }
+    }
+
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let quarantine = sibling_path(file_path, &format!("corrupt-{}", stamp));
+    fs::rename(file_path, &quarantine)?;
+
+    let recovered = todos.len();
+    save_todos(file_path, &TodoFile { version: SCHEMA_VERSION, next_id, todos })?;
+
+    let mut report = format!(
+        "{} could not be loaded ({}).\nRecovered {} task(s); the damaged file was moved to {}.",
+        file_path.display(),
+        load_error,
+        recovered,
+        quarantine.display()
+    );
+    if let Some(previous) = previous {
+        report.push_str(&format!(
+            "\nThe last good save ({} tasks) is still available in {}.",
+            previous.todos.len(),
+            backup.display()
+        ));
+    }
+    Ok(report)
+}
+
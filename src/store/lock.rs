@@ -0,0 +1,101 @@
+//! Advisory locking of the todo file, so concurrent invocations take turns
+//! instead of overwriting each other's changes.
+
+use std::env;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::sibling_path;
+use crate::Error;
+
+/// How long to wait for another invocation to release the todo file
+/// before giving up. Can be overridden with TODO_LOCK_TIMEOUT (seconds).
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An advisory lock on the todo file, held for a whole load -> modify -> save
+/// cycle so concurrent invocations can't overwrite each other's changes.
+///
+/// The lock lives on a separate `<file>.lock` file because `save_todos`
+/// replaces the data file with a rename, which would silently drop a lock
+/// taken on the old file. Dropping the guard releases the lock.
+pub struct FileLock {
+    file: File,
+    exclusive: bool,
+}
+
+impl FileLock {
+    /// Lock the todo file, waiting up to the lock timeout for other
+    /// invocations to finish. Readers take a shared lock so several `list`s
+    /// can run at once; anything that writes takes an exclusive one.
+    pub fn acquire(file_path: &Path, exclusive: bool) -> Result<FileLock, Error> {
+        if let Some(folder) = file_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(folder)
+                .map_err(|e| Error::Io(io::Error::new(e.kind(), format!("cannot create {}: {}", folder.display(), e))))?;
+        }
+        let lock_path = sibling_path(file_path, "lock");
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .map_err(|e| Error::Io(io::Error::new(e.kind(), format!("cannot open {}: {}", lock_path.display(), e))))?;
+
+        let timeout = lock_timeout()?;
+        // A timeout too long to add up means waiting for as long as it takes
+        let deadline = Instant::now().checked_add(timeout);
+
+        loop {
+            let attempt = if exclusive { file.try_lock() } else { file.try_lock_shared() };
+            match attempt {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) if deadline.is_none_or(|deadline| Instant::now() < deadline) => {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(TryLockError::WouldBlock) => {
+                    // Writers record their PID in the lock file so we can say who's holding it
+                    let holder = fs::read_to_string(&lock_path).unwrap_or_default();
+                    let message = match holder.trim().parse::<u32>() {
+                        Ok(pid) => format!("todo list is locked by PID {} (gave up after {:?})", pid, timeout),
+                        Err(_) => format!("todo list is locked by another process (gave up after {:?})", timeout),
+                    };
+                    return Err(Error::Lock(message));
+                }
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+
+        if exclusive {
+            file.set_len(0)?;
+            write!(file, "{}", std::process::id())?;
+        }
+        Ok(FileLock { file, exclusive })
+    }
+}
+
+/// The lock timeout from TODO_LOCK_TIMEOUT, if set: a number of seconds
+/// that isn't negative, NaN or infinite.
+pub(super) fn lock_timeout() -> Result<Duration, Error> {
+    let Ok(secs) = env::var("TODO_LOCK_TIMEOUT") else {
+        return Ok(DEFAULT_LOCK_TIMEOUT);
+    };
+    secs.trim()
+        .parse()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| Error::Validation(format!("invalid TODO_LOCK_TIMEOUT `{}` (expected a number of seconds)", secs)))
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Clear our PID so a stale one is never reported; the OS releases the
+        // lock itself when the file is closed, even if the process crashes
+        if self.exclusive {
+            let _ = self.file.set_len(0);
+        }
+        let _ = self.file.unlock();
+    }
+}
+
@@ -0,0 +1,169 @@
+//! Persistence for the todo list.
+//!
+//! Everything that reads or writes todos goes through the `TodoStore` trait,
+//! so the rest of the program doesn't care how (or where) they are kept.
+//! The backend is picked with `"backend"` in the config file.
+
+mod events;
+mod json;
+mod lock;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use events::{Event, EventStore};
+pub use json::{repair_file, JsonStore};
+pub(crate) use json::{migrate_todos, SCHEMA_VERSION};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Todo};
+
+/// A place todos can be loaded from and saved to.
+///
+/// Changes made through `insert`, `update` and `delete` may be buffered by the
+/// backend; they are only guaranteed to be on disk after `commit` succeeds.
+pub trait TodoStore {
+    /// Every todo that matches `filter`, in the order they were added.
+    fn query(&self, filter: &dyn Fn(&Todo) -> bool) -> Result<Vec<Todo>, Error>;
+
+    /// Store a new todo. The store assigns the ID (whatever `todo.id` holds
+    /// is ignored) and returns the todo as it was stored.
+    fn insert(&mut self, todo: Todo) -> Result<Todo, Error>;
+
+    /// Replace the stored todo that has the same ID as `todo`.
+    fn update(&mut self, todo: &Todo) -> Result<(), Error>;
+
+    /// Delete the todo with the given ID and return it.
+    fn delete(&mut self, id: u64) -> Result<Todo, Error>;
+
+    /// Put back a deleted todo under its original ID (used by undo and
+    /// redo). Fails if a todo with that ID already exists.
+    fn restore(&mut self, todo: &Todo) -> Result<(), Error>;
+
+    /// Make every change since the store was opened durable.
+    fn commit(&mut self) -> Result<(), Error>;
+
+    /// Every todo, in the order they were added.
+    fn load(&self) -> Result<Vec<Todo>, Error> {
+        self.query(&|_| true)
+    }
+
+    /// The todo with the given ID, if there is one. Backends that can look
+    /// a single todo up should, rather than filtering the whole list.
+    fn get(&self, id: u64) -> Result<Option<Todo>, Error> {
+        Ok(self.query(&|todo| todo.id == id)?.pop())
+    }
+
+    /// The direct subtasks of the todo with the given ID, in the order they
+    /// were added.
+    fn subtasks(&self, id: u64) -> Result<Vec<Todo>, Error> {
+        self.query(&|todo| todo.parent == Some(id))
+    }
+}
+
+/// Which `TodoStore` implementation to use, as named in the config file.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// A single JSON document, rewritten on every change (the default).
+    #[default]
+    Json,
+    /// An embedded SQLite database that updates rows in place.
+    /// Only available when built with `--features sqlite`.
+    Sqlite,
+    /// An append-only JSON-lines log of events, folded into the list on load.
+    Events,
+}
+
+impl Backend {
+    /// File name used for the data in the XDG data directory.
+    pub fn default_file_name(self) -> &'static str {
+        match self {
+            Backend::Json => "todos.json",
+            Backend::Sqlite => "todos.db",
+            Backend::Events => "todos.events.jsonl",
+        }
+    }
+}
+
+/// Open the store for `path` with the given backend.
+///
+/// The store holds a lock on the data until it is dropped: pass `exclusive`
+/// for anything that writes, so concurrent invocations can't lose updates.
+pub fn open(backend: Backend, path: &Path, exclusive: bool) -> Result<Box<dyn TodoStore>, Error> {
+    match backend {
+        Backend::Json => Ok(Box::new(JsonStore::open(path, exclusive)?)),
+        Backend::Events => Ok(Box::new(EventStore::open(path, exclusive)?)),
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => Ok(Box::new(SqliteStore::open(path, exclusive)?)),
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => Err(Error::Validation(
+            "this build has no SQLite support; rebuild with `cargo build --features sqlite`".to_string(),
+        )),
+    }
+}
+
+/// Build the path of a file that lives next to the todo file,
+/// e.g. `todos.json` + `bak` -> `todos.json.bak`.
+pub(crate) fn sibling_path(file_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = file_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    file_path.with_file_name(name)
+}
+
+/// Replace the file at `path` with `contents`, crash-safely.
+///
+/// The contents go to a temp file in the same directory, which is fsynced and
+/// then renamed over the real file, so readers only ever see the old or the
+/// new contents, never a truncated mix. The new file keeps the old one's
+/// permissions. With `backup`, the previous version is first copied to
+/// `<file>.bak`.
+pub fn replace_file(file_path: &Path, contents: &str, backup: bool) -> Result<(), Error> {
+    let tmp_path = sibling_path(file_path, &format!("tmp-{}", std::process::id()));
+    let written = File::create(&tmp_path).and_then(|mut tmp| {
+        // Before any contents go in, so a private list is never readable by others
+        match fs::metadata(file_path) {
+            Ok(metadata) => tmp.set_permissions(metadata.permissions())?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        tmp.write_all(contents.as_bytes())?;
+        tmp.sync_all()
+    });
+    if let Err(e) = written {
+        // Don't leave half-written temp files lying around (e.g. on a full disk)
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e.into());
+    }
+
+    // Rotate the backup: copy rather than rename, so the real file never disappears
+    if backup && file_path.exists() {
+        fs::copy(file_path, sibling_path(file_path, "bak"))?;
+    }
+
+    // rename() is atomic when both paths are on the same filesystem
+    fs::rename(&tmp_path, file_path)?;
+    sync_parent_dir(file_path)?;
+    Ok(())
+}
+
+/// fsync the directory holding `file_path` so the rename itself is durable.
+/// Directories can't be opened as files on Windows, so this is Unix-only.
+#[cfg(unix)]
+fn sync_parent_dir(file_path: &Path) -> std::io::Result<()> {
+    match file_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_file_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
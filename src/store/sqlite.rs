@@ -0,0 +1,420 @@
+//! Embedded SQLite backend, compiled in with `--features sqlite`.
+//!
+//! Each todo is one row that is updated in place, so changing a single task
+//! no longer rewrites the whole list. The row keeps the todo as a JSON column
+//! next to its ID: new `Todo` fields then need no `ALTER TABLE`, and old rows
+//! are upgraded with the same migrations as the JSON file. The database's
+//! schema version lives in `PRAGMA user_version`. A single todo is looked up
+//! by its ID, and subtasks through an index on their parent, so neither
+//! reads the whole table.
+//!
+//! The bindings below cover just the handful of C functions this needs and
+//! link against the system libsqlite3.
+
+use serde_json::{json, Value};
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::ptr;
+use std::time::Duration;
+
+use super::json::{migrate_todos, SCHEMA_VERSION};
+use super::lock::{lock_timeout, FileLock};
+use super::TodoStore;
+use crate::{Error, Todo};
+
+mod ffi {
+    use std::ffi::{c_char, c_int, c_void};
+
+    /// Opaque `sqlite3` connection handle.
+    pub enum Sqlite3 {}
+    /// Opaque `sqlite3_stmt` prepared statement handle.
+    pub enum Stmt {}
+
+    pub const SQLITE_OK: c_int = 0;
+    pub const SQLITE_ROW: c_int = 100;
+    pub const SQLITE_DONE: c_int = 101;
+    pub const SQLITE_OPEN_READWRITE: c_int = 0x2;
+    pub const SQLITE_OPEN_CREATE: c_int = 0x4;
+    /// Destructor value telling SQLite to copy bound text right away.
+    pub const SQLITE_TRANSIENT: isize = -1;
+
+    #[link(name = "sqlite3")]
+    extern "C" {
+        pub fn sqlite3_open_v2(filename: *const c_char, db: *mut *mut Sqlite3, flags: c_int, vfs: *const c_char) -> c_int;
+        pub fn sqlite3_close(db: *mut Sqlite3) -> c_int;
+        pub fn sqlite3_errmsg(db: *mut Sqlite3) -> *const c_char;
+        pub fn sqlite3_busy_timeout(db: *mut Sqlite3, ms: c_int) -> c_int;
+        pub fn sqlite3_exec(
+            db: *mut Sqlite3,
+            sql: *const c_char,
+            callback: *const c_void,
+            arg: *mut c_void,
+            errmsg: *mut *mut c_char,
+        ) -> c_int;
+        pub fn sqlite3_prepare_v2(
+            db: *mut Sqlite3,
+            sql: *const c_char,
+            nbyte: c_int,
+            stmt: *mut *mut Stmt,
+            tail: *mut *const c_char,
+        ) -> c_int;
+        pub fn sqlite3_bind_int64(stmt: *mut Stmt, index: c_int, value: i64) -> c_int;
+        pub fn sqlite3_bind_text(stmt: *mut Stmt, index: c_int, text: *const c_char, len: c_int, destructor: isize) -> c_int;
+        pub fn sqlite3_step(stmt: *mut Stmt) -> c_int;
+        pub fn sqlite3_column_int64(stmt: *mut Stmt, column: c_int) -> i64;
+        pub fn sqlite3_column_text(stmt: *mut Stmt, column: c_int) -> *const u8;
+        pub fn sqlite3_column_bytes(stmt: *mut Stmt, column: c_int) -> c_int;
+        pub fn sqlite3_finalize(stmt: *mut Stmt) -> c_int;
+        pub fn sqlite3_last_insert_rowid(db: *mut Sqlite3) -> i64;
+        pub fn sqlite3_changes(db: *mut Sqlite3) -> c_int;
+    }
+}
+
+/// SQL text as a C string. Our statements are literals, so a NUL is a bug.
+fn sql_string(sql: &str) -> Result<CString, Error> {
+    CString::new(sql).map_err(|_| Error::Validation("SQL must not contain NUL bytes".to_string()))
+}
+
+/// An open database connection, closed when dropped.
+struct Connection {
+    db: *mut ffi::Sqlite3,
+}
+
+impl Connection {
+    fn open(path: &Path) -> Result<Connection, Error> {
+        let name = path.to_str()
+            .and_then(|name| CString::new(name).ok())
+            .ok_or_else(|| Error::Validation(format!("unsupported database path {}", path.display())))?;
+        let mut db = ptr::null_mut();
+        let flags = ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE;
+        // SAFETY: `name` is a valid C string and `db` a valid out-pointer.
+        let rc = unsafe { ffi::sqlite3_open_v2(name.as_ptr(), &mut db, flags, ptr::null()) };
+        // SQLite hands back a handle even on failure so the error can be read
+        let conn = Connection { db };
+        if rc != ffi::SQLITE_OK {
+            return Err(conn.error());
+        }
+        Ok(conn)
+    }
+
+    /// Wait up to `timeout` when another connection holds the database,
+    /// instead of failing right away with "database is locked".
+    fn busy_timeout(&self, timeout: Duration) -> Result<(), Error> {
+        let ms = c_int::try_from(timeout.as_millis()).unwrap_or(c_int::MAX);
+        // SAFETY: the connection is open.
+        match unsafe { ffi::sqlite3_busy_timeout(self.db, ms) } {
+            ffi::SQLITE_OK => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+
+    /// The message for the most recent failed call on this connection.
+    fn error(&self) -> Error {
+        if self.db.is_null() {
+            return Error::Io(io::Error::other("out of memory opening the SQLite database"));
+        }
+        // SAFETY: errmsg always returns a valid, NUL-terminated string.
+        let message = unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.db)) };
+        Error::Io(io::Error::other(format!("SQLite: {}", message.to_string_lossy())))
+    }
+
+    /// Run one or more statements that take no parameters.
+    fn execute(&self, sql: &str) -> Result<(), Error> {
+        let sql = sql_string(sql)?;
+        // SAFETY: the connection is open and `sql` is a valid C string.
+        let rc = unsafe { ffi::sqlite3_exec(self.db, sql.as_ptr(), ptr::null(), ptr::null_mut(), ptr::null_mut()) };
+        if rc != ffi::SQLITE_OK {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+
+    fn prepare(&self, sql: &str) -> Result<Statement<'_>, Error> {
+        let sql = sql_string(sql)?;
+        let mut stmt = ptr::null_mut();
+        // SAFETY: the connection is open and both pointers are valid.
+        let rc = unsafe { ffi::sqlite3_prepare_v2(self.db, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()) };
+        if rc != ffi::SQLITE_OK {
+            return Err(self.error());
+        }
+        Ok(Statement { conn: self, stmt })
+    }
+
+    fn user_version(&self) -> Result<u64, Error> {
+        let mut stmt = self.prepare("PRAGMA user_version")?;
+        stmt.step()?;
+        Ok(stmt.column_i64(0) as u64)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Closing with an open transaction rolls it back, which is exactly
+        // what should happen to changes that were never committed
+        // SAFETY: every statement borrows the connection, so all are finalized by now.
+        unsafe { ffi::sqlite3_close(self.db) };
+    }
+}
+
+/// A prepared statement, finalized when dropped.
+struct Statement<'c> {
+    conn: &'c Connection,
+    stmt: *mut ffi::Stmt,
+}
+
+impl Statement<'_> {
+    fn bind_i64(&mut self, index: c_int, value: i64) -> Result<(), Error> {
+        // SAFETY: the statement is live; SQLite checks the index.
+        match unsafe { ffi::sqlite3_bind_int64(self.stmt, index, value) } {
+            ffi::SQLITE_OK => Ok(()),
+            _ => Err(self.conn.error()),
+        }
+    }
+
+    fn bind_text(&mut self, index: c_int, text: &str) -> Result<(), Error> {
+        let len = c_int::try_from(text.len())
+            .map_err(|_| Error::Validation("text too long for SQLite".to_string()))?;
+        // SAFETY: SQLITE_TRANSIENT makes SQLite copy the bytes before returning.
+        let rc = unsafe {
+            ffi::sqlite3_bind_text(self.stmt, index, text.as_ptr() as *const c_char, len, ffi::SQLITE_TRANSIENT)
+        };
+        match rc {
+            ffi::SQLITE_OK => Ok(()),
+            _ => Err(self.conn.error()),
+        }
+    }
+
+    /// Advance the statement. Returns true while there is a row to read.
+    fn step(&mut self) -> Result<bool, Error> {
+        // SAFETY: the statement is live.
+        match unsafe { ffi::sqlite3_step(self.stmt) } {
+            ffi::SQLITE_ROW => Ok(true),
+            ffi::SQLITE_DONE => Ok(false),
+            _ => Err(self.conn.error()),
+        }
+    }
+
+    fn column_i64(&self, column: c_int) -> i64 {
+        // SAFETY: only called after `step` returned a row.
+        unsafe { ffi::sqlite3_column_int64(self.stmt, column) }
+    }
+
+    fn column_text(&self, column: c_int) -> String {
+        // SAFETY: only called after `step` returned a row; the text pointer
+        // stays valid until the next step, and we copy it out immediately.
+        unsafe {
+            let text = ffi::sqlite3_column_text(self.stmt, column);
+            if text.is_null() {
+                return String::new();
+            }
+            let len = ffi::sqlite3_column_bytes(self.stmt, column) as usize;
+            String::from_utf8_lossy(std::slice::from_raw_parts(text, len)).into_owned()
+        }
+    }
+}
+
+impl Drop for Statement<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the statement was created by prepare_v2 and is finalized once.
+        unsafe { ffi::sqlite3_finalize(self.stmt) };
+    }
+}
+
+/// Todos kept in an SQLite database, one row per todo.
+///
+/// Everything done through a store opened with `exclusive` runs in a single
+/// write transaction that `commit` makes durable; dropping the store without
+/// committing rolls back. Shared (read-only) stores only read.
+pub struct SqliteStore {
+    conn: Connection,
+    /// Whether the store holds the write transaction, or only reads.
+    exclusive: bool,
+    _lock: FileLock,
+}
+
+impl SqliteStore {
+    /// Lock the database at `path` and open it, creating it if needed.
+    pub fn open(path: &Path, exclusive: bool) -> Result<SqliteStore, Error> {
+        let mut lock = FileLock::acquire(path, exclusive)?;
+        if let Some(folder) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(folder)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(lock_timeout()?)?;
+
+        let version = conn.user_version()?;
+        if version > SCHEMA_VERSION {
+            return Err(newer_version(version));
+        }
+        if version < SCHEMA_VERSION || !has_parent_index(&conn)? {
+            if exclusive {
+                upgrade(&conn)?;
+            } else {
+                // Setting up the schema writes, so a reader takes a writer's
+                // turn for it (letting go of its shared lock first, so two
+                // readers never wait for each other)
+                drop(lock);
+                let writer = FileLock::acquire(path, true)?;
+                upgrade(&conn)?;
+                drop(writer);
+                lock = FileLock::acquire(path, false)?;
+            }
+        }
+        // A reader's transaction only reads, so any number of them can run
+        conn.execute(if exclusive { "BEGIN IMMEDIATE;" } else { "BEGIN;" })?;
+        Ok(SqliteStore { conn, exclusive, _lock: lock })
+    }
+}
+
+/// Create the table and index of a new database, or bring an older one up
+/// to date, rewriting every row in the current schema. The caller must hold
+/// the exclusive lock.
+fn upgrade(conn: &Connection) -> Result<(), Error> {
+    conn.execute("BEGIN IMMEDIATE;")?;
+    // Another invocation may have done it while this one waited for the lock
+    let version = conn.user_version()?;
+    if version > SCHEMA_VERSION {
+        return Err(newer_version(version));
+    }
+    if version == SCHEMA_VERSION && has_parent_index(conn)? {
+        return conn.execute("COMMIT;");
+    }
+
+    // AUTOINCREMENT keeps SQLite from ever handing out a deleted ID again.
+    // The index serves `subtasks`, whose WHERE clause must use the same
+    // expression.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS todos (id INTEGER PRIMARY KEY AUTOINCREMENT, todo TEXT NOT NULL);
+         CREATE INDEX IF NOT EXISTS todos_parent ON todos (json_extract(todo, '$.parent'));",
+    )?;
+    // A brand-new database (version 0) has nothing to migrate
+    if version > 0 {
+        let mut rows = Vec::new();
+        let mut select = conn.prepare("SELECT id, todo FROM todos ORDER BY id")?;
+        while select.step()? {
+            rows.push(row_value(select.column_i64(0), &select.column_text(1))?);
+        }
+        drop(select);
+        for todo in migrate_todos(version, rows)? {
+            let mut update = conn.prepare("UPDATE todos SET todo = ?2 WHERE id = ?1")?;
+            update.bind_i64(1, todo.id as i64)?;
+            update.bind_text(2, &row_data(&todo)?)?;
+            update.step()?;
+        }
+    }
+    if version != SCHEMA_VERSION {
+        conn.execute(&format!("PRAGMA user_version = {};", SCHEMA_VERSION))?;
+    }
+    conn.execute("COMMIT;")
+}
+
+/// Whether the database has the index on todos' parents (databases created
+/// before it was added don't).
+fn has_parent_index(conn: &Connection) -> Result<bool, Error> {
+    conn.prepare("SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = 'todos_parent'")?.step()
+}
+
+fn newer_version(version: u64) -> Error {
+    Error::Parse(format!(
+        "the database uses schema version {}, but this build only understands up to version {}; please upgrade cli-todo-rust",
+        version, SCHEMA_VERSION
+    ))
+}
+
+/// Rebuild a todo's JSON from its row; the ID lives in its own column.
+fn row_value(id: i64, data: &str) -> Result<Value, Error> {
+    let mut todo: Value = serde_json::from_str(data)?;
+    todo["id"] = json!(id);
+    Ok(todo)
+}
+
+/// Every todo `select` returns, as `(id, todo)` rows.
+fn read_todos(mut select: Statement<'_>) -> Result<Vec<Todo>, Error> {
+    let mut todos = Vec::new();
+    while select.step()? {
+        todos.push(serde_json::from_value(row_value(select.column_i64(0), &select.column_text(1))?)?);
+    }
+    Ok(todos)
+}
+
+/// The JSON stored for a todo: everything except the ID column.
+fn row_data(todo: &Todo) -> Result<String, Error> {
+    let mut data = serde_json::to_value(todo)?;
+    if let Some(fields) = data.as_object_mut() {
+        fields.remove("id");
+    }
+    Ok(data.to_string())
+}
+
+impl TodoStore for SqliteStore {
+    fn query(&self, filter: &dyn Fn(&Todo) -> bool) -> Result<Vec<Todo>, Error> {
+        let mut todos = Vec::new();
+        let mut select = self.conn.prepare("SELECT id, todo FROM todos ORDER BY id")?;
+        while select.step()? {
+            let todo: Todo = serde_json::from_value(row_value(select.column_i64(0), &select.column_text(1))?)?;
+            if filter(&todo) {
+                todos.push(todo);
+            }
+        }
+        Ok(todos)
+    }
+
+    fn get(&self, id: u64) -> Result<Option<Todo>, Error> {
+        let mut select = self.conn.prepare("SELECT id, todo FROM todos WHERE id = ?1")?;
+        select.bind_i64(1, id as i64)?;
+        Ok(read_todos(select)?.pop())
+    }
+
+    fn subtasks(&self, id: u64) -> Result<Vec<Todo>, Error> {
+        let mut select = self.conn
+            .prepare("SELECT id, todo FROM todos WHERE json_extract(todo, '$.parent') = ?1 ORDER BY id")?;
+        select.bind_i64(1, id as i64)?;
+        read_todos(select)
+    }
+
+    fn insert(&mut self, mut todo: Todo) -> Result<Todo, Error> {
+        let mut insert = self.conn.prepare("INSERT INTO todos (todo) VALUES (?1)")?;
+        insert.bind_text(1, &row_data(&todo)?)?;
+        insert.step()?;
+        // SAFETY: the connection is open.
+        todo.id = unsafe { ffi::sqlite3_last_insert_rowid(self.conn.db) } as u64;
+        Ok(todo)
+    }
+
+    fn update(&mut self, todo: &Todo) -> Result<(), Error> {
+        let mut update = self.conn.prepare("UPDATE todos SET todo = ?2 WHERE id = ?1")?;
+        update.bind_i64(1, todo.id as i64)?;
+        update.bind_text(2, &row_data(todo)?)?;
+        update.step()?;
+        // SAFETY: the connection is open.
+        if unsafe { ffi::sqlite3_changes(self.conn.db) } == 0 {
+            return Err(Error::NotFound(todo.id.to_string()));
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, id: u64) -> Result<Todo, Error> {
+        let todo = self.get(id)?.ok_or_else(|| Error::NotFound(id.to_string()))?;
+        let mut delete = self.conn.prepare("DELETE FROM todos WHERE id = ?1")?;
+        delete.bind_i64(1, id as i64)?;
+        delete.step()?;
+        Ok(todo)
+    }
+
+    fn restore(&mut self, todo: &Todo) -> Result<(), Error> {
+        if self.get(todo.id)?.is_some() {
+            return Err(Error::Validation(format!("task {} already exists", todo.id)));
+        }
+        let mut insert = self.conn.prepare("INSERT INTO todos (id, todo) VALUES (?1, ?2)")?;
+        insert.bind_i64(1, todo.id as i64)?;
+        insert.bind_text(2, &row_data(todo)?)?;
+        insert.step()?;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), Error> {
+        self.conn.execute(if self.exclusive { "COMMIT; BEGIN IMMEDIATE;" } else { "COMMIT; BEGIN;" })
+    }
+}
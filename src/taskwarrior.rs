@@ -0,0 +1,243 @@
+//! Reading and writing the JSON of [Taskwarrior](https://taskwarrior.org)'s
+//! `task export` and `task import`: an array with one object per task.
+//!
+//! | Taskwarrior | `Todo` |
+//! |-------------|--------|
+//! | `description`, `project`, `tags` | `description`, `project`, `tags` |
+//! | `status` `pending` (`waiting`, `recurring`) | `status` todo, or in progress if `start` is set |
+//! | `status` `completed`, `deleted` | `status` done, cancelled |
+//! | `priority` `H`, `M`, `L` | `priority` |
+//! | `due` | `due` (its UTC date) |
+//! | `entry`, `modified`, `end` | `created_at`, `updated_at`, `completed_at` |
+//! | `cli_todo_rust_status` | `blocked`, which Taskwarrior has no status for |
+//! | `uuid`, `annotations` and anything else | `extra` |
+//!
+//! Everything without a `Todo` field is kept in `extra` and written back on
+//! export, so nothing a task came with is lost: its `uuid`, `annotations`,
+//! `wait`, `depends`, recurrence and user-defined attributes, and the exact
+//! time of a `due` that isn't midnight UTC. Only `id` and `urgency`, which
+//! Taskwarrior recomputes, are dropped.
+
+use serde_json::{Map, Value};
+
+use crate::ical::{date_time, parse_date_time};
+use crate::{Error, Priority, Status, Timestamp, Todo};
+
+const STATUS: &str = "cli_todo_rust_status";
+
+/// The `uuid` a todo is exported with. Tasks from Taskwarrior keep theirs;
+/// others get one made from their creation time and ID, which stays the
+/// same from one export to the next.
+pub fn uuid(todo: &Todo) -> String {
+    match todo.extra.get("uuid").and_then(Value::as_str) {
+        Some(uuid) => uuid.to_string(),
+        None => {
+            let created = todo.created_at.map_or(0, Timestamp::unix);
+            format!("{:08x}-{:04x}-4000-8000-{:012x}", created as u32, (created >> 32) as u16, todo.id)
+        }
+    }
+}
+
+/// Write todos as a `task import` file, one task per line.
+pub fn format(todos: &[Todo]) -> String {
+    let tasks: Vec<String> = todos.iter().map(|todo| Value::Object(task(todo)).to_string()).collect();
+    format!("[\n{}\n]\n", tasks.join(",\n"))
+}
+
+/// A todo as a Taskwarrior task object.
+fn task(todo: &Todo) -> Map<String, Value> {
+    let mut task: Map<String, Value> = todo.extra.clone().into_iter().collect();
+    let time = |timestamp: Timestamp| Value::from(date_time(timestamp));
+    let updated = todo.updated_at.unwrap_or_else(Timestamp::now);
+
+    task.insert("uuid".to_string(), Value::from(uuid(todo)));
+    task.insert("description".to_string(), Value::from(todo.description.clone()));
+    // Taskwarrior needs an entry date, so a task created at an unknown
+    // time is said to have been created when it last changed
+    task.insert("entry".to_string(), time(todo.created_at.unwrap_or(updated)));
+    task.extend(todo.updated_at.map(|t| ("modified".to_string(), time(t))));
+
+    let status = match todo.status {
+        Status::Done => "completed",
+        Status::Cancelled => "deleted",
+        // Keep `waiting` and `recurring` from Taskwarrior
+        _ => task.get("status").and_then(Value::as_str).unwrap_or("pending"),
+    };
+    task.insert("status".to_string(), Value::from(status));
+    task.remove(STATUS);
+    if todo.status == Status::Blocked {
+        task.insert(STATUS.to_string(), Value::from(todo.status.as_str()));
+    }
+    // `start` marks a task as active
+    match todo.status {
+        Status::InProgress => {
+            task.entry("start").or_insert_with(|| time(updated));
+        }
+        _ => {
+            task.remove("start");
+        }
+    }
+    match todo.status {
+        Status::Done => {
+            task.insert("end".to_string(), time(todo.completed_at.unwrap_or(updated)));
+        }
+        Status::Cancelled => {
+            task.entry("end").or_insert_with(|| time(updated));
+        }
+        _ => {
+            task.remove("end");
+        }
+    }
+
+    // The exact due time kept from an import, if it is still the same day
+    let kept = task
+        .remove("due")
+        .and_then(|due| due.as_str().and_then(parse_date_time))
+        .filter(|due| Some(due.date()) == todo.due);
+    if let Some(date) = todo.due {
+        task.insert("due".to_string(), time(kept.unwrap_or(Timestamp::start_of(date))));
+    }
+    // Otherwise `extra` may hold a priority level that has no match here
+    if let Some(priority) = todo.priority {
+        task.insert("priority".to_string(), Value::from(priority.as_str()));
+    }
+    task.remove("tags");
+    if !todo.tags.is_empty() {
+        task.insert("tags".to_string(), Value::from(todo.tags.clone()));
+    }
+    task.remove("project");
+    task.extend(todo.project.as_ref().map(|project| ("project".to_string(), Value::from(project.clone()))));
+    task
+}
+
+/// Read a `task export` file: a JSON array of tasks, or one task object per
+/// line. Each todo has ID 0 and its `uuid` in `extra`, for matching it
+/// against the tasks already stored. Errors name the task by its position.
+pub fn parse(text: &str) -> Result<Vec<Todo>, Error> {
+    let invalid = |e: serde_json::Error| Error::Validation(format!("invalid Taskwarrior JSON: {}", e));
+    let tasks: Vec<Map<String, Value>> = if text.trim_start().starts_with('[') {
+        serde_json::from_str(text).map_err(invalid)?
+    } else {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line.trim().trim_end_matches(',')).map_err(invalid))
+            .collect::<Result<_, _>>()?
+    };
+    tasks
+        .into_iter()
+        .enumerate()
+        .map(|(i, task)| todo(task).map_err(|e| Error::Validation(format!("task {}: {}", i + 1, e))))
+        .collect()
+}
+
+/// The stored todo `existing` updated with what an imported copy of it
+/// says. Its ID, creation time and parent task are kept, and the imported
+/// `extra` fields are added to its own (except a `uuid` it already has,
+/// generated or not).
+pub fn merge(existing: &Todo, imported: &Todo) -> Todo {
+    let own = uuid(existing);
+    let mut extra = existing.extra.clone();
+    for (key, value) in &imported.extra {
+        if key != "uuid" || *value != own {
+            extra.insert(key.clone(), value.clone());
+        }
+    }
+    Todo {
+        id: existing.id,
+        created_at: existing.created_at.or(imported.created_at),
+        updated_at: existing.updated_at,
+        completed_at: imported.completed_at.or(existing.completed_at).filter(|_| imported.status == Status::Done),
+        parent: existing.parent,
+        extra,
+        ..imported.clone()
+    }
+}
+
+/// A Taskwarrior task object as a todo.
+fn todo(mut task: Map<String, Value>) -> Result<Todo, Error> {
+    let mut todo = Todo::new("");
+    task.remove("id");
+    task.remove("urgency");
+
+    todo.description = string(&mut task, "description")?
+        .ok_or_else(|| Error::Validation("the task has no description".to_string()))?;
+    let status = string(&mut task, "status")?;
+    todo.status = match status.as_deref() {
+        None | Some("pending") => Status::Todo,
+        Some("completed") => Status::Done,
+        Some("deleted") => Status::Cancelled,
+        Some(status @ ("waiting" | "recurring")) => {
+            todo.extra.insert("status".to_string(), Value::from(status));
+            Status::Todo
+        }
+        Some(other) => return Err(Error::Validation(format!("unknown status `{}`", other))),
+    };
+    if todo.status == Status::Todo {
+        let status = string(&mut task, STATUS)?.and_then(|status| status.parse::<Status>().ok());
+        if let Some(status) = status.filter(|status| status.is_open()) {
+            todo.status = status;
+        } else if task.contains_key("start") {
+            todo.status = Status::InProgress;
+        }
+    }
+
+    todo.created_at = timestamp(&mut task, "entry")?;
+    todo.updated_at = timestamp(&mut task, "modified")?;
+    if todo.status == Status::Done {
+        todo.completed_at = timestamp(&mut task, "end")?;
+    }
+    if let Some(due) = timestamp(&mut task, "due")? {
+        todo.due = Some(due.date());
+        if due != Timestamp::start_of(due.date()) {
+            todo.extra.insert("due".to_string(), Value::from(date_time(due)));
+        }
+    }
+    match task.remove("priority") {
+        Some(Value::String(priority)) if Priority::ALL.iter().any(|level| level.as_str() == priority) => {
+            todo.priority = Some(priority.parse()?);
+        }
+        Some(Value::Null) | None => {}
+        Some(other) => {
+            todo.extra.insert("priority".to_string(), other);
+        }
+    }
+    match task.remove("tags") {
+        Some(Value::Array(tags)) => {
+            for tag in tags {
+                let Value::String(tag) = tag else {
+                    return Err(Error::Validation("`tags` must be an array of strings".to_string()));
+                };
+                if !todo.tags.contains(&tag) {
+                    todo.tags.push(tag);
+                }
+            }
+        }
+        Some(Value::Null) | None => {}
+        Some(_) => return Err(Error::Validation("`tags` must be an array of strings".to_string())),
+    }
+    todo.project = string(&mut task, "project")?;
+
+    todo.extra.extend(task);
+    todo.validate()?;
+    Ok(todo)
+}
+
+/// Take a string attribute out of `task`.
+fn string(task: &mut Map<String, Value>, key: &str) -> Result<Option<String>, Error> {
+    match task.remove(key) {
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(Value::Null) | None => Ok(None),
+        Some(other) => Err(Error::Validation(format!("`{}` must be a string, not {}", key, other))),
+    }
+}
+
+/// Take a date attribute, e.g. `20261015T091244Z`, out of `task`.
+fn timestamp(task: &mut Map<String, Value>, key: &str) -> Result<Option<Timestamp>, Error> {
+    string(task, key)?
+        .map(|value| {
+            parse_date_time(&value)
+                .or_else(|| value.parse().ok())
+                .ok_or_else(|| Error::Validation(format!("invalid `{}` date `{}`", key, value)))
+        })
+        .transpose()
+}
@@ -0,0 +1,237 @@
+//! Reading and writing the [todo.txt](https://github.com/todotxt/todo.txt)
+//! format, one task per line:
+//!
+//! ```text
+//! (A) 2026-10-01 Call the landlord @phone +home due:2026-10-20
+//! x 2026-10-15 2026-10-01 Pay rent @online +home pri:B
+//! ```
+//!
+//! | todo.txt | `Todo` |
+//! |----------|--------|
+//! | `x` at the start | `status` done (cancelled with `status:cancelled`) |
+//! | `(A)`, `(B)`, `(C)` (`pri:A`... on completed tasks) | `priority` H, M, L |
+//! | `(D)` to `(Z)` | kept in `extra` as `pri` |
+//! | completion date, creation date | `completed_at`, `created_at` (midnight UTC) |
+//! | the last `+project` | `project`; any other `+project` stays in the description |
+//! | `@context` | `tags` |
+//! | `due:YYYY-MM-DD` | `due` |
+//! | `status:in-progress`, `status:blocked`, `status:cancelled` | `status` |
+//! | any other `key:value` | `extra` |
+//! | `\` before a word, e.g. `\chapter:2` | the word, in the description |
+//!
+//! Every line this module writes reads back as the same todo, apart from
+//! the ID (todo.txt has none), `updated_at`, the time of day of the two
+//! timestamps, since todo.txt only records days, and runs of spaces in the
+//! description. Description words that would read back as something else
+//! (`+project`, `@context` or `key:value` words, or a leading `x`, priority
+//! or date) are written with a `\` in front. Reading a line and writing it
+//! again gives the same tokens, with the description first and the others
+//! in a fixed order after it.
+
+use serde_json::Value;
+
+use crate::{Date, Error, Priority, Status, Timestamp, Todo};
+
+/// Parse every non-blank line of a todo.txt file. Errors name the line.
+pub fn parse(text: &str) -> Result<Vec<Todo>, Error> {
+    let mut todos = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let todo = parse_line(line).map_err(|e| Error::Validation(format!("line {}: {}", number + 1, e)))?;
+        todos.push(todo);
+    }
+    Ok(todos)
+}
+
+/// Write todos as a todo.txt file, one line each.
+pub fn format(todos: &[Todo]) -> String {
+    todos.iter().map(|todo| format_line(todo) + "\n").collect()
+}
+
+/// Parse one todo.txt line into a todo (with ID 0).
+pub fn parse_line(line: &str) -> Result<Todo, Error> {
+    let mut todo = Todo::new("");
+    let mut words = line.split_whitespace().peekable();
+    let mut created = None;
+    let mut completed = None;
+    let mut letter = None;
+
+    let closed = words.next_if_eq(&"x").is_some();
+    if closed {
+        todo.status = Status::Done;
+        completed = words.next_if(|word| is_date(word)).map(parse_date).transpose()?;
+        if completed.is_some() {
+            created = words.next_if(|word| is_date(word)).map(parse_date).transpose()?;
+        }
+    } else {
+        letter = words.next_if(|word| priority_letter(word).is_some()).and_then(priority_letter);
+        created = words.next_if(|word| is_date(word)).map(parse_date).transpose()?;
+    }
+
+    // Only the last +project becomes the project, so the others must stay
+    // where they are in the description
+    let words: Vec<&str> = words.collect();
+    let project = words.iter().rposition(|word| project_name(word).is_some());
+    let mut description = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        if let Some(text) = word.strip_prefix('\\') {
+            description.push(text);
+        } else if Some(i) == project {
+            todo.project = project_name(word).map(str::to_string);
+        } else if let Some(tag) = word.strip_prefix('@').filter(|tag| !tag.is_empty()) {
+            if !todo.tags.iter().any(|t| t == tag) {
+                todo.tags.push(tag.to_string());
+            }
+        } else if let Some((key, value)) = extension(word) {
+            match key {
+                "due" if is_date(value) => todo.due = Some(parse_date(value)?),
+                "pri" if closed && priority_letter(&format!("({})", value)).is_some() => {
+                    letter = value.chars().next();
+                }
+                "status" => match value.parse::<Status>() {
+                    Ok(status) => todo.status = status,
+                    Err(_) => {
+                        todo.extra.insert(key.to_string(), Value::from(value));
+                    }
+                },
+                // Only written for closed tasks that have no completion date
+                "created" if created.is_none() && is_date(value) => created = Some(parse_date(value)?),
+                _ => {
+                    todo.extra.insert(key.to_string(), Value::from(value));
+                }
+            }
+        } else {
+            description.push(*word);
+        }
+    }
+    todo.description = description.join(" ");
+
+    match letter {
+        Some('A') => todo.priority = Some(Priority::High),
+        Some('B') => todo.priority = Some(Priority::Medium),
+        Some('C') => todo.priority = Some(Priority::Low),
+        Some(letter) => {
+            todo.extra.insert("pri".to_string(), Value::from(letter.to_string()));
+        }
+        None => {}
+    }
+    todo.created_at = created.map(Timestamp::start_of);
+    todo.completed_at = completed.map(Timestamp::start_of);
+    todo.validate()?;
+    Ok(todo)
+}
+
+/// Write a todo as one todo.txt line.
+pub fn format_line(todo: &Todo) -> String {
+    let letter = match todo.priority {
+        Some(Priority::High) => Some("A".to_string()),
+        Some(Priority::Medium) => Some("B".to_string()),
+        Some(Priority::Low) => Some("C".to_string()),
+        None => todo.extra.get("pri").and_then(Value::as_str).map(str::to_string),
+    };
+    let created = todo.created_at.map(|t| t.date().to_string());
+    let mut words = Vec::new();
+    let mut extensions = Vec::new();
+
+    // Whether a date, or an `x` or priority, right at the start of the
+    // description would be read as one of the leading tokens
+    let (takes_date, takes_start) = if todo.status.is_open() {
+        (created.is_none(), created.is_none() && letter.is_none())
+    } else {
+        (todo.completed_at.is_none() || created.is_none(), false)
+    };
+    let description: Vec<String> = todo
+        .description
+        .split_whitespace()
+        .enumerate()
+        .map(|(i, word)| {
+            let misread = word.starts_with('\\')
+                || (todo.project.is_none() && project_name(word).is_some())
+                || word.strip_prefix('@').is_some_and(|tag| !tag.is_empty())
+                || extension(word).is_some()
+                || (i == 0 && takes_date && is_date(word))
+                || (i == 0 && takes_start && (word == "x" || priority_letter(word).is_some()));
+            if misread {
+                format!("\\{}", word)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect();
+
+    if todo.status.is_open() {
+        words.extend(letter.map(|letter| format!("({})", letter)));
+        words.extend(created);
+    } else {
+        words.push("x".to_string());
+        // A creation date can only be written after a completion date
+        match todo.completed_at {
+            Some(completed) => {
+                words.push(completed.date().to_string());
+                words.extend(created);
+            }
+            None => extensions.extend(created.map(|created| format!("created:{}", created))),
+        }
+        extensions.extend(letter.map(|letter| format!("pri:{}", letter)));
+    }
+    words.push(description.join(" "));
+    words.extend(todo.project.iter().map(|project| format!("+{}", project)));
+    words.extend(todo.tags.iter().map(|tag| format!("@{}", tag)));
+    words.extend(todo.due.map(|due| format!("due:{}", due)));
+    if !matches!(todo.status, Status::Todo | Status::Done) {
+        words.push(format!("status:{}", todo.status));
+    }
+    words.extend(extensions);
+    for (key, value) in &todo.extra {
+        if key == "pri" {
+            continue;
+        }
+        // Values that aren't a single word can't be written as `key:value`
+        let value = match value {
+            Value::String(value) => value.clone(),
+            Value::Number(_) | Value::Bool(_) => value.to_string(),
+            _ => continue,
+        };
+        if !value.is_empty() && !value.contains(char::is_whitespace) {
+            words.push(format!("{}:{}", key, value));
+        }
+    }
+    words.join(" ")
+}
+
+/// `(A)` -> `A`, for the letters A to Z.
+fn priority_letter(word: &str) -> Option<char> {
+    let letter = word.strip_prefix('(')?.strip_suffix(')')?;
+    let mut chars = letter.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) if letter.is_ascii_uppercase() => Some(letter),
+        _ => None,
+    }
+}
+
+/// `+home` -> `home`.
+fn project_name(word: &str) -> Option<&str> {
+    word.strip_prefix('+').filter(|project| !project.is_empty())
+}
+
+/// A `key:value` extension: the key is a word starting with a letter and the
+/// value isn't empty. Times like `10:30` and URLs like `https://...` are
+/// description text, not extensions.
+fn extension(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    let key_ok = key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    (key_ok && !value.is_empty() && !value.starts_with("//")).then_some((key, value))
+}
+
+/// True for a word shaped like `YYYY-MM-DD`.
+fn is_date(word: &str) -> bool {
+    word.len() == 10
+        && word.char_indices().all(|(i, c)| if i == 4 || i == 7 { c == '-' } else { c.is_ascii_digit() })
+}
+
+fn parse_date(word: &str) -> Result<Date, Error> {
+    word.parse()
+}
@@ -0,0 +1,33 @@
+//! Helpers shared by the integration tests.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A fresh, empty directory in the temp directory, removed with everything
+/// in it when dropped (even if the test panics).
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// `cli-todo-rust-<test file>-<name>-<pid>`, emptied if an earlier run
+    /// left it behind.
+    pub fn new(name: &str) -> TempDir {
+        let dir = format!("cli-todo-rust-{}-{}-{}", env!("CARGO_CRATE_NAME"), name, std::process::id());
+        let path = std::env::temp_dir().join(dir);
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    /// The path of `file` inside the directory.
+    pub fn join(&self, file: impl AsRef<Path>) -> PathBuf {
+        self.path.join(file)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,63 @@
+//! Tests for the CSV and TSV import and export: quoting, header mapping and
+//! per-row errors.
+
+use cli_todo_rust::csv::{self, Field};
+use cli_todo_rust::{Date, Priority, Status, Timestamp, Todo};
+
+fn today() -> Date {
+    "2026-10-15".parse().unwrap()
+}
+
+#[test]
+fn todos_round_trip() {
+    let mut full = Todo::new("Pay rent, \"now\"");
+    full.status = Status::Done;
+    full.priority = Some(Priority::High);
+    full.due = Some("2026-10-20".parse().unwrap());
+    full.tags = vec!["home".to_string(), "bills".to_string()];
+    full.project = Some("flat".to_string());
+    full.created_at = Some("2026-10-01T08:00:00Z".parse::<Timestamp>().unwrap());
+    full.updated_at = Some("2026-10-15T09:30:00Z".parse().unwrap());
+    full.completed_at = full.updated_at;
+    let todos = vec![full, Todo::new(" Leading space"), Todo::new("Bare")];
+
+    for delimiter in [',', '\t'] {
+        let text = csv::format(&todos, &Field::ALL, delimiter);
+        let rows = csv::parse(&text, delimiter, &[], today()).unwrap();
+        assert!(rows.errors.is_empty() && rows.ignored.is_empty());
+        // Cells are trimmed on the way in
+        let expected: Vec<Todo> = todos
+            .iter()
+            .map(|todo| Todo { description: todo.description.trim().to_string(), ..todo.clone() })
+            .collect();
+        assert_eq!(rows.todos, expected, "through\n{}", text);
+    }
+}
+
+#[test]
+fn headers_are_mapped_and_bad_rows_reported() {
+    let text = "\u{feff}Task,When,Labels,Notes\r\n\
+                \"Call, then write\",2026-10-20,\"a, +b\",ignored\r\n\
+                Bad date,2026-02-30,,\r\n\
+                ,,,\r\n\
+                \"Two\nlines\",,,\r\n";
+    let mapping = [
+        ("task".to_string(), Field::Description),
+        ("WHEN".to_string(), Field::Due),
+        ("Labels".to_string(), Field::Tags),
+    ];
+    let rows = csv::parse(text, ',', &mapping, today()).unwrap();
+
+    assert_eq!(rows.ignored, ["Notes"]);
+    assert_eq!(rows.todos.len(), 1);
+    assert_eq!(rows.todos[0].description, "Call, then write");
+    assert_eq!(rows.todos[0].tags, ["a", "b"]);
+    assert_eq!(rows.errors, [
+        "line 3: due: 2026-02-30 is not a valid date",
+        "line 5: the description must be a single line",
+    ]);
+
+    // Without a description column nothing can be imported
+    assert!(csv::parse("Task,When\nCall,\n", ',', &[], today()).is_err());
+    assert!(csv::parse("description\n\"unterminated\n", ',', &[], today()).is_err());
+}
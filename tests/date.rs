@@ -0,0 +1,117 @@
+//! Tests for the natural-language date parser: every phrase `--due` takes,
+//! and dates that would leave the years `YYYY-MM-DD` can write.
+
+use cli_todo_rust::{Date, Error, Timestamp};
+
+fn date(s: &str) -> Date {
+    s.parse().unwrap()
+}
+
+/// Parse relative to Thursday, 2026-10-15.
+fn parse(input: &str) -> Result<Date, Error> {
+    Date::parse(input, date("2026-10-15"))
+}
+
+#[test]
+fn phrases_are_resolved_against_today() {
+    let cases = [
+        ("2026-11-01", "2026-11-01"),
+        ("today", "2026-10-15"),
+        ("  Tomorrow ", "2026-10-16"),
+        ("yesterday", "2026-10-14"),
+        ("friday", "2026-10-16"),
+        ("next fri", "2026-10-16"),
+        // Today's weekday means next week's
+        ("thursday", "2026-10-22"),
+        ("wed", "2026-10-21"),
+        ("next week", "2026-10-22"),
+        ("next month", "2026-11-15"),
+        ("next year", "2027-10-15"),
+        ("in 3 days", "2026-10-18"),
+        ("in 1 day", "2026-10-16"),
+        ("in 2 weeks", "2026-10-29"),
+        ("in a month", "2026-11-15"),
+        ("in one year", "2027-10-15"),
+        ("in -3 days", "2026-10-12"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(parse(input).unwrap(), date(expected), "{}", input);
+    }
+}
+
+#[test]
+fn months_are_clamped_to_their_last_day() {
+    let end_of_january = date("2026-01-31");
+    assert_eq!(Date::parse("next month", end_of_january).unwrap(), date("2026-02-28"));
+    assert_eq!(Date::parse("in 1 month", date("2028-01-31")).unwrap(), date("2028-02-29"));
+    assert_eq!(Date::parse("in 13 months", end_of_january).unwrap(), date("2027-02-28"));
+    assert_eq!(Date::parse("next year", date("2028-02-29")).unwrap(), date("2029-02-28"));
+}
+
+#[test]
+fn unknown_phrases_are_rejected() {
+    for input in ["", "someday", "in three days", "in 3 fortnights", "next decade", "2026-13-01", "2026-1-5"] {
+        assert!(matches!(parse(input), Err(Error::Validation(_))), "{}", input);
+    }
+    assert_eq!(parse("2026-02-30").unwrap_err().to_string(), "2026-02-30 is not a valid date");
+}
+
+#[test]
+fn dates_stay_within_four_digit_years() {
+    for input in [
+        "in 10000 years",
+        "in -2026 years",
+        "in 9223372036854775807 days",
+        "in -9223372036854775808 days",
+        "in 9223372036854775807 weeks",
+        "in 9223372036854775807 months",
+        "in 9223372036854775807 years",
+        "0000-01-01",
+        "12026-10-15",
+    ] {
+        assert!(matches!(parse(input), Err(Error::Validation(_))), "{}", input);
+    }
+    let error = Date::parse("tomorrow", date("9999-12-31")).unwrap_err();
+    assert_eq!(error.to_string(), "the date `tomorrow` is out of range (years 1 to 9999)");
+    assert!(Date::parse("yesterday", date("0001-01-01")).is_err());
+    assert_eq!(parse("in 7973 years").unwrap(), date("9999-10-15"));
+
+    // Every date that parses can be written and read back
+    let last = date("9999-12-31");
+    assert_eq!(last.to_string().parse::<Date>().unwrap(), last);
+    assert!("9999-12-31T23:00:00-05:00".parse::<Timestamp>().is_err());
+    assert!("0001-01-01T01:00:00+02:00".parse::<Timestamp>().is_err());
+}
+
+#[test]
+fn timestamp_offsets_are_hours_and_minutes_within_a_day() {
+    let utc: Timestamp = "2026-10-15T09:30:00Z".parse().unwrap();
+    for input in [
+        "2026-10-15T09:30:00z",
+        "2026-10-15 09:30:00.250Z",
+        "2026-10-15T15:00:00+05:30",
+        "2026-10-14T23:31:00-09:59",
+        "2026-10-15T09:30:00-00:00",
+    ] {
+        assert_eq!(input.parse::<Timestamp>().unwrap(), utc, "{}", input);
+    }
+    assert_eq!("2026-10-16T09:29:00+23:59".parse::<Timestamp>().unwrap(), utc);
+
+    for input in [
+        "2026-01-01T00:00:00+99999999999999999:00",
+        "2026-01-01T00:00:00-9223372036854775807:00",
+        "2026-01-01T00:00:00+00:9223372036854775807",
+        "2026-01-01T00:00:00+24:00",
+        "2026-01-01T00:00:00+01:60",
+        "2026-01-01T00:00:00+1:00",
+        "2026-01-01T00:00:00+0100",
+        "2026-01-01T00:00:00+01:00:00",
+        "2026-01-01T00:00:00+-1:00",
+        "2026-01-01T00:00:00+01",
+        "2026-01-01T00:00:00",
+    ] {
+        let error = input.parse::<Timestamp>().unwrap_err();
+        assert!(matches!(error, Error::Validation(_)), "{}: {}", input, error);
+        assert!(error.to_string().starts_with(&format!("invalid timestamp `{}`", input)), "{}", error);
+    }
+}
@@ -0,0 +1,117 @@
+//! Tests for the TOML document `edit` opens in `$EDITOR`: every todo reads
+//! back as itself, hand edits in any valid form apply, and anything outside
+//! the supported subset is rejected rather than guessed at.
+
+use cli_todo_rust::edit::{from_toml, to_toml};
+use cli_todo_rust::{Error, Priority, Status, Todo};
+
+/// Task 7, with every editable field set.
+fn full() -> Todo {
+    Todo {
+        id: 7,
+        status: Status::Blocked,
+        priority: Some(Priority::Low),
+        due: Some("2026-11-01".parse().unwrap()),
+        tags: vec!["home".to_string(), "say\"hi\"".to_string(), "c:\\temp".to_string()],
+        project: Some("flat#2".to_string()),
+        parent: Some(3),
+        ..Todo::new("Pay \"rent\" \\ deposit\tby # friday ✓ \u{7}\u{85}")
+    }
+}
+
+#[test]
+fn rendered_todos_read_back_unchanged() {
+    let mut done = Todo { id: 1, status: Status::Done, ..Todo::new("Call mom") };
+    done.extra.insert("uda".to_string(), serde_json::json!(1));
+    for todo in [full(), done, Todo::new("'single' [quotes] = fine")] {
+        let text = to_toml(&todo);
+        assert_eq!(from_toml(&todo, &text).unwrap(), Some(todo.clone()), "{}", text);
+    }
+}
+
+#[test]
+fn hand_edits_in_any_supported_form_apply() {
+    let text = r#"
+        # Fields can come in any order, with comments and odd spacing
+        tags = [ 'lit\eral' ,"\u00e9t\U0001F600", ]   # trailing comma
+        project=''
+        status = "in-progress"
+            description = 'C:\path\with "quotes"'
+        priority = "H"
+        due = ""
+    "#;
+    let edited = from_toml(&full(), text).unwrap().unwrap();
+    assert_eq!(edited.description, r#"C:\path\with "quotes""#);
+    assert_eq!(edited.status, Status::InProgress);
+    assert_eq!(edited.priority, Some(Priority::High));
+    assert_eq!(edited.due, None);
+    assert_eq!(edited.tags, ["lit\\eral", "ét😀"]);
+    assert_eq!(edited.project, None);
+    // What the document doesn't cover is kept
+    assert_eq!((edited.id, edited.parent), (7, Some(3)));
+}
+
+#[test]
+fn an_empty_document_cancels() {
+    for text in ["", "\n  \n", "# Editing task 7.\n# nothing left\n"] {
+        assert_eq!(from_toml(&full(), text).unwrap(), None);
+    }
+}
+
+#[test]
+fn invalid_documents_are_rejected() {
+    let base = to_toml(&full());
+    // The rendered document with the line for `key` replaced (or dropped)
+    let with = |key: &str, line: &str| -> String {
+        let prefix = format!("{} =", key);
+        base.lines()
+            .filter_map(|l| if l.starts_with(&prefix) { (!line.is_empty()).then_some(line) } else { Some(l) })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let cases = [
+        // Escapes
+        (with("description", r#"description = "bad \x escape""#), r"line 4: invalid escape \x"),
+        (with("description", r#"description = "\u12""#), r"invalid escape \u"),
+        (with("description", r#"description = "\u+041""#), r"invalid escape \u+041"),
+        (with("description", r#"description = "\uD800""#), r"invalid escape \uD800"),
+        (with("description", r#"description = "\U00110000""#), r"invalid escape \U00110000"),
+        (with("description", r#"description = "ends in \"#), "unterminated string"),
+        // Strings
+        (with("description", r#"description = "Pay rent"#), "unterminated string"),
+        (with("description", "description = 'Pay rent"), "unterminated string"),
+        (with("description", "description = Pay rent"), "invalid value `Pay` (strings need quotes)"),
+        (with("description", r#"description = "Pay" "rent""#), "unexpected text after the value of `description`"),
+        (with("description", r#"description = "Pay rent" // note"#), "unexpected text after"),
+        (with("description", r#"description = """#), "the description must not be empty"),
+        (with("description", r#"description = "two\nlines""#), "must be a single line"),
+        // Comments
+        (with("description", r#"description # = "Pay rent""#), "expected `=` after `description`"),
+        (with("description", r#"description = # "Pay rent""#), "invalid value ``"),
+        // Arrays
+        (with("tags", r#"tags = "home""#), "`tags` must be an array of strings, not a string"),
+        (with("tags", r#"tags = ["home", 1]"#), "must be an array of strings, not an integer"),
+        (with("tags", r#"tags = [["home"]]"#), "must be an array of strings, not an array"),
+        (with("tags", r#"tags = ["home""#), "expected `,` or `]`"),
+        (with("tags", r#"tags = ["home" "work"]"#), "expected `,` or `]`"),
+        (with("tags", "tags = [  # one per line"), "invalid value ``"),
+        (with("tags", r#"tags = ["two words"]"#), "invalid tag `two words`"),
+        (with("description", r#"description = ["Pay rent"]"#), "`description` must be a string, not an array"),
+        // Keys and values
+        (with("status", "status = true"), "`status` must be a string, not true or false"),
+        (with("status", r#"status = "asleep""#), "unknown status `asleep`"),
+        (with("priority", "priority = 1"), "`priority` must be a string, not an integer"),
+        (with("due", r#"due = "someday""#), "someday"),
+        (with("project", ""), "missing field `project`"),
+        (with("project", r#""project" = "flat""#), "expected a field name"),
+        (with("project", r#"project.name = "flat""#), "expected `=` after `project`"),
+        (base.clone() + "colour = \"red\"\n", "unknown field `colour`"),
+        (base.clone() + "status = \"done\"\n", "line 10: `status` is set twice"),
+    ];
+    for (text, message) in cases {
+        let error = from_toml(&full(), &text).unwrap_err();
+        assert!(matches!(error, Error::Validation(_)), "{}: {}", error, text);
+        let error = error.to_string();
+        assert!(error.contains(message), "{:?} does not mention {:?} in:\n{}", error, message, text);
+    }
+}
@@ -0,0 +1,263 @@
+//! Tests for the event-log backend: folding the log onto its snapshot,
+//! skipping a line cut off by a crash, compacting, loading merged branches,
+//! and refusing to start from an empty list when the log or snapshot is
+//! damaged.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cli_todo_rust::store::{self, Backend, EventStore};
+use cli_todo_rust::{Error, Status, Timestamp, Todo};
+use serde_json::Value;
+
+mod common;
+use common::TempDir;
+
+/// A fresh directory, and the log's path inside it.
+fn temp_log(name: &str) -> (TempDir, PathBuf) {
+    let dir = TempDir::new(name);
+    let log = dir.join("todos.events.jsonl");
+    (dir, log)
+}
+
+#[test]
+fn damaged_old_snapshots_are_errors() {
+    let (dir, log) = temp_log("snapshot");
+    let snapshot = dir.join("todos.events.jsonl.snapshot");
+    let damaged = r#"{"version": 3, "next_id": 4, "last_seq": 7, "todos": {"1": "Pay rent"}}"#;
+    fs::write(&snapshot, damaged).unwrap();
+
+    let error = store::open(Backend::Events, &log, true).err().unwrap();
+    assert!(matches!(error, Error::Parse(_)), "{}", error);
+    assert!(error.to_string().contains("invalid `todos` in the snapshot"), "{}", error);
+    // Nothing was compacted over it
+    assert_eq!(fs::read_to_string(&snapshot).unwrap(), damaged);
+
+    fs::write(&snapshot, r#"{"version": 3, "next_id": 4, "last_seq": 7}"#).unwrap();
+    assert!(matches!(store::open(Backend::Events, &log, true), Err(Error::Parse(_))));
+}
+
+/// The `event` of every line in the log.
+fn events(log: &Path) -> Vec<String> {
+    let text = fs::read_to_string(log).unwrap_or_default();
+    text.lines().map(|line| serde_json::from_str::<Value>(line).unwrap()["event"].as_str().unwrap().to_string()).collect()
+}
+
+#[test]
+fn the_log_folds_into_the_list() {
+    let (_dir, log) = temp_log("fold");
+    let mut store = store::open(Backend::Events, &log, true).unwrap();
+    for description in ["Pay rent", "Call mom", "Water plants"] {
+        store.insert(Todo::new(description)).unwrap();
+    }
+    let now = Timestamp::now();
+    let mut rent = store.query(&|todo| todo.id == 1).unwrap().pop().unwrap();
+    rent.status = Status::Done;
+    rent.updated_at = Some(now);
+    rent.completed_at = Some(now);
+    store.update(&rent).unwrap();
+    let mut mom = store.query(&|todo| todo.id == 2).unwrap().pop().unwrap();
+    mom.description = "Call mom back".to_string();
+    store.update(&mom).unwrap();
+    let plants = store.delete(3).unwrap();
+    store.delete(2).unwrap();
+    store.restore(&mom).unwrap();
+    store.commit().unwrap();
+    let expected = store.load().unwrap();
+    drop(store);
+
+    // A plain completion is logged as such, anything else as a whole todo
+    assert_eq!(events(&log), ["added", "added", "added", "completed", "edited", "removed", "removed", "added"]);
+    assert_eq!(expected, [rent, mom]);
+    let mut store = store::open(Backend::Events, &log, true).unwrap();
+    assert_eq!(store.load().unwrap(), expected);
+    // IDs aren't reused, even for a removed last task
+    assert_eq!(store.insert(Todo::new("Water plants")).unwrap().id, plants.id + 1);
+    drop(store);
+
+    // An event that can't apply to the list so far names its line
+    let damaged = [
+        ("{\"seq\": 1, \"v\": 1, \"ts\": 0, \"user\": \"ann\", \"event\": \"removed\", \"id\": 1}\n", "line 1: "),
+        ("{\"seq\": 1, \"event\": \"added\"}\n{}\n", "line 1: "),
+    ];
+    for (text, message) in damaged {
+        fs::write(&log, text).unwrap();
+        let error = store::open(Backend::Events, &log, true).err().unwrap();
+        assert!(matches!(error, Error::Parse(_)), "{}", error);
+        assert!(error.to_string().contains(message), "{}", error);
+    }
+}
+
+#[test]
+fn a_truncated_last_line_is_skipped() {
+    let (_dir, log) = temp_log("truncated");
+    let mut store = store::open(Backend::Events, &log, true).unwrap();
+    store.insert(Todo::new("Pay rent")).unwrap();
+    store.insert(Todo::new("Call mom")).unwrap();
+    store.commit().unwrap();
+    drop(store);
+    let complete = fs::read_to_string(&log).unwrap();
+    let second = complete.lines().nth(1).unwrap();
+    let crashed = format!("{}{}", complete, &second[..second.len() / 2]);
+    fs::write(&log, &crashed).unwrap();
+
+    // Readers skip the partial line but leave the file alone
+    let store = store::open(Backend::Events, &log, false).unwrap();
+    assert_eq!(store.load().unwrap().len(), 2);
+    drop(store);
+    assert_eq!(fs::read_to_string(&log).unwrap(), crashed);
+
+    // A writer cuts it off, so the next event starts on a line of its own
+    let mut store = store::open(Backend::Events, &log, true).unwrap();
+    assert_eq!(fs::read_to_string(&log).unwrap(), complete);
+    store.insert(Todo::new("Water plants")).unwrap();
+    store.commit().unwrap();
+    drop(store);
+    assert_eq!(events(&log), ["added", "added", "added"]);
+    let store = store::open(Backend::Events, &log, false).unwrap();
+    assert_eq!(store.load().unwrap().iter().map(|todo| todo.id).collect::<Vec<_>>(), [1, 2, 3]);
+    drop(store);
+
+    // A whole last line without its newline counts; a writer puts the
+    // newline back before appending after it
+    let unterminated = fs::read_to_string(&log).unwrap().trim_end().to_string();
+    fs::write(&log, &unterminated).unwrap();
+    let store = store::open(Backend::Events, &log, false).unwrap();
+    assert_eq!(store.load().unwrap().len(), 3);
+    drop(store);
+    assert_eq!(fs::read_to_string(&log).unwrap(), unterminated);
+    let mut store = store::open(Backend::Events, &log, true).unwrap();
+    assert_eq!(fs::read_to_string(&log).unwrap(), unterminated.clone() + "\n");
+    store.insert(Todo::new("Sweep")).unwrap();
+    store.commit().unwrap();
+    drop(store);
+    assert_eq!(events(&log), ["added", "added", "added", "added"]);
+    assert_eq!(store::open(Backend::Events, &log, false).unwrap().load().unwrap().len(), 4);
+
+    // Only a partial last line is forgiven
+    let lines: Vec<&str> = complete.lines().collect();
+    for text in [format!("{}\n{}\n", &lines[0][..10], lines[1]), format!("{}\n", &lines[0][..10])] {
+        fs::write(&log, &text).unwrap();
+        let error = store::open(Backend::Events, &log, true).err().unwrap();
+        assert!(matches!(error, Error::Parse(_)), "{}", error);
+        assert!(error.to_string().contains("line 1: "), "{}", error);
+        assert_eq!(fs::read_to_string(&log).unwrap(), text);
+    }
+}
+
+#[test]
+fn compaction_archives_the_folded_events() {
+    let (dir, log) = temp_log("compact");
+    let snapshot = dir.join("todos.events.jsonl.snapshot");
+    let archive = dir.join("todos.events.jsonl.archive");
+
+    let mut store = store::open(Backend::Events, &log, true).unwrap();
+    for i in 1..500 {
+        store.insert(Todo::new(format!("Task {}", i))).unwrap();
+    }
+    store.commit().unwrap();
+    assert!(!snapshot.exists());
+
+    // The 500th event in the log triggers it
+    store.insert(Todo::new("Task 500")).unwrap();
+    store.commit().unwrap();
+    let expected = store.load().unwrap();
+    drop(store);
+    assert!(snapshot.exists());
+    assert_eq!(fs::read_to_string(&log).unwrap(), "");
+    assert_eq!(events(&archive).len(), 500);
+
+    let mut store = store::open(Backend::Events, &log, true).unwrap();
+    assert_eq!(store.load().unwrap(), expected);
+    store.delete(1).unwrap();
+    store.commit().unwrap();
+    drop(store);
+    assert_eq!(events(&log), ["removed"]);
+
+    // Explicit compaction; a crash before the log was emptied leaves events
+    // behind that the snapshot already covers
+    let mut store = EventStore::open(&log, true).unwrap();
+    let before = fs::read_to_string(&log).unwrap();
+    assert_eq!(store.compact().unwrap(), 1);
+    drop(store);
+    assert_eq!(events(&archive).len(), 501);
+    fs::write(&log, &before).unwrap();
+
+    let mut store = store::open(Backend::Events, &log, true).unwrap();
+    assert_eq!(store.load().unwrap().len(), 499);
+    assert_eq!(store.insert(Todo::new("Task 501")).unwrap().id, 501);
+    store.commit().unwrap();
+    drop(store);
+    let store = store::open(Backend::Events, &log, false).unwrap();
+    let todos = store.load().unwrap();
+    assert_eq!((todos.len(), todos[0].id, todos[499].id), (500, 2, 501));
+    drop(store);
+}
+
+#[test]
+fn merged_branches_keep_both_tasks() {
+    let dir = TempDir::new("merge");
+    let base = dir.join("base.jsonl");
+    let mut store = store::open(Backend::Events, &base, true).unwrap();
+    store.insert(Todo::new("Pay rent")).unwrap();
+    store.commit().unwrap();
+    drop(store);
+    let base_text = fs::read_to_string(&base).unwrap();
+
+    // Each branch adds a task 2; theirs also gives it a subtask and edits it
+    let ours = dir.join("ours.jsonl");
+    fs::write(&ours, &base_text).unwrap();
+    let mut store = store::open(Backend::Events, &ours, true).unwrap();
+    let mut mom = store.insert(Todo::new("Call mom")).unwrap();
+    mom.status = Status::Blocked;
+    store.update(&mom).unwrap();
+    store.commit().unwrap();
+    drop(store);
+
+    let theirs = dir.join("theirs.jsonl");
+    fs::write(&theirs, &base_text).unwrap();
+    let mut store = store::open(Backend::Events, &theirs, true).unwrap();
+    let mut plants = store.insert(Todo::new("Water plants")).unwrap();
+    let fern = store.insert(Todo { parent: Some(plants.id), ..Todo::new("The fern") }).unwrap();
+    plants.description = "Water the plants".to_string();
+    store.update(&plants).unwrap();
+    store.commit().unwrap();
+    drop(store);
+    assert_eq!((mom.id, plants.id, fern.id), (2, 2, 3));
+
+    // What `merge=union` leaves: the base, then each side's new lines
+    let log = dir.join("todos.events.jsonl");
+    let new_lines = |path: &Path| fs::read_to_string(path).unwrap()[base_text.len()..].to_string();
+    let merged = base_text.clone() + &new_lines(&ours) + &new_lines(&theirs);
+    fs::write(&log, &merged).unwrap();
+
+    let summary = |todos: Vec<Todo>| -> Vec<(u64, String, Option<u64>, Status)> {
+        todos.into_iter().map(|todo| (todo.id, todo.description, todo.parent, todo.status)).collect()
+    };
+    let expected = vec![
+        (1, "Pay rent".to_string(), None, Status::Todo),
+        (2, "Call mom".to_string(), None, Status::Blocked),
+        (3, "Water the plants".to_string(), None, Status::Todo),
+        (4, "The fern".to_string(), Some(3), Status::Todo),
+    ];
+    // Readers see the same renumbering, and leave the log as it is
+    let store = store::open(Backend::Events, &log, false).unwrap();
+    assert_eq!(summary(store.load().unwrap()), expected);
+    drop(store);
+    assert_eq!(fs::read_to_string(&log).unwrap(), merged);
+
+    // A writer notes it, so task 2 means "Call mom" again from then on
+    let mut store = store::open(Backend::Events, &log, true).unwrap();
+    assert_eq!(events(&log)[6..], ["renumbered", "renumbered"]);
+    mom.status = Status::Done;
+    store.update(&mom).unwrap();
+    assert_eq!(store.insert(Todo::new("Sweep")).unwrap().id, 5);
+    store.commit().unwrap();
+    drop(store);
+
+    let store = store::open(Backend::Events, &log, false).unwrap();
+    let todos = summary(store.load().unwrap());
+    assert_eq!(todos[1], (2, "Call mom".to_string(), None, Status::Done));
+    assert_eq!(todos[2..4], expected[2..4]);
+    assert_eq!(todos.len(), 5);
+}
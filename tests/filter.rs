@@ -0,0 +1,143 @@
+//! Tests for the filter language: how expressions are tokenized and
+//! grouped, what each term means, and which inputs are rejected.
+
+use cli_todo_rust::filter::{Comparison, Filter, Term};
+use cli_todo_rust::{Date, Error, Priority, Status, Todo};
+
+fn date(s: &str) -> Date {
+    s.parse().unwrap()
+}
+
+/// Parse relative to Thursday, 2026-10-15.
+fn parse(input: &str) -> Result<Filter, Error> {
+    Filter::parse(input, date("2026-10-15"))
+}
+
+fn term(term: Term) -> Filter {
+    Filter::Term(term)
+}
+
+fn text(text: &str) -> Filter {
+    term(Term::Text(text.to_string()))
+}
+
+fn and(left: Filter, right: Filter) -> Filter {
+    Filter::And(Box::new(left), Box::new(right))
+}
+
+fn or(left: Filter, right: Filter) -> Filter {
+    Filter::Or(Box::new(left), Box::new(right))
+}
+
+fn not(inner: Filter) -> Filter {
+    Filter::Not(Box::new(inner))
+}
+
+#[test]
+fn and_binds_tighter_than_or() {
+    let cases = [
+        ("a b or not c", or(and(text("a"), text("b")), not(text("c")))),
+        ("a AND b Or c and d", or(and(text("a"), text("b")), and(text("c"), text("d")))),
+        ("a and (b or c)", and(text("a"), or(text("b"), text("c")))),
+        ("not not a", not(not(text("a")))),
+        ("not (a or b) c", and(not(or(text("a"), text("b"))), text("c"))),
+        ("((a))", text("a")),
+        ("a or b or c", or(or(text("a"), text("b")), text("c"))),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(parse(input).unwrap(), expected, "{}", input);
+    }
+}
+
+#[test]
+fn terms_are_parsed() {
+    let cases = [
+        ("status:open", Term::Open(true)),
+        ("STATUS:Closed", Term::Open(false)),
+        ("status:in-progress", Term::Status(Status::InProgress)),
+        ("tag:+work", Term::Tag("work".to_string())),
+        ("project:@home", Term::Project(Some("home".to_string()))),
+        ("project:none", Term::Project(None)),
+        ("priority:H", Term::Priority(Some(Priority::High))),
+        ("priority:None", Term::Priority(None)),
+        ("due:2026-11-01", Term::Due(Comparison::On, date("2026-11-01"))),
+        ("due:=tomorrow", Term::Due(Comparison::On, date("2026-10-16"))),
+        ("due:<2026-11-01", Term::Due(Comparison::Before, date("2026-11-01"))),
+        ("due:<=2026-11-01", Term::Due(Comparison::OnOrBefore, date("2026-11-01"))),
+        ("due:>=2026-11-01", Term::Due(Comparison::OnOrAfter, date("2026-11-01"))),
+        ("due:>\"next friday\"", Term::Due(Comparison::After, date("2026-10-16"))),
+        ("due:any", Term::HasDue(true)),
+        ("due:none", Term::HasDue(false)),
+        ("due:overdue", Term::Overdue(date("2026-10-15"))),
+        ("id:42", Term::Id(42)),
+        ("Invoice", Term::Text("invoice".to_string())),
+        // Quoted text is never a keyword or a field
+        ("\"Re: Invoice (2)\"", Term::Text("re: invoice (2)".to_string())),
+        ("\"and\"", Term::Text("and".to_string())),
+        ("\"say \\\"hi\\\"\"", Term::Text("say \"hi\"".to_string())),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(parse(input).unwrap(), term(expected), "{}", input);
+    }
+}
+
+#[test]
+fn bad_filters_are_rejected() {
+    let cases = [
+        ("", "expected a term, found the end of the filter"),
+        ("a and", "expected a term, found the end of the filter"),
+        ("not", "expected a term, found the end of the filter"),
+        ("or a", "expected a term, found `or`"),
+        ("(a or b", "missing `)`"),
+        ("a)", "unmatched `)`"),
+        ("()", "expected a term, found `)`"),
+        ("\"open", "unterminated quote"),
+        ("colour:red", "unknown field `colour`"),
+        ("id:seven", "invalid ID `seven`"),
+        ("status:waiting", "unknown status"),
+        ("priority:X", "priority"),
+        ("due:<someday", "can't understand the date `someday`"),
+        ("due:\"in 10000 years\"", "out of range"),
+    ];
+    for (input, message) in cases {
+        let error = parse(input).unwrap_err();
+        assert!(matches!(error, Error::Validation(_)), "{}: {}", input, error);
+        let text = error.to_string();
+        assert!(text.starts_with(&format!("invalid filter `{}`: ", input)), "{}", text);
+        assert!(text.contains(message), "{}: {}", input, text);
+    }
+}
+
+#[test]
+fn filters_pick_the_matching_todos() {
+    let todos = [
+        Todo { id: 1, tags: vec!["work".to_string()], priority: Some(Priority::High), ..Todo::new("Send invoice") },
+        Todo { id: 2, due: Some(date("2026-10-10")), project: Some("home".to_string()), ..Todo::new("Fix the sink") },
+        Todo { id: 3, status: Status::Done, due: Some(date("2026-10-01")), ..Todo::new("Pay INVOICE 7") },
+        Todo {
+            id: 4,
+            status: Status::Blocked,
+            due: Some(date("2026-10-20")),
+            tags: vec!["work".to_string(), "q4".to_string()],
+            ..Todo::new("Plan offsite")
+        },
+    ];
+
+    let cases: [(&str, &[u64]); 10] = [
+        ("invoice", &[1, 3]),
+        ("status:open invoice", &[1]),
+        ("status:closed or status:blocked", &[3, 4]),
+        ("tag:work and not tag:q4", &[1]),
+        ("project:none due:any", &[3, 4]),
+        ("due:overdue", &[2]),
+        ("due:<2026-10-15", &[2, 3]),
+        ("due:>=2026-10-10 or priority:H", &[1, 2, 4]),
+        ("not (id:1 or id:2)", &[3, 4]),
+        ("priority:none due:none", &[]),
+    ];
+    for (input, expected) in cases {
+        let filter = parse(input).unwrap();
+        let matched: Vec<u64> = todos.iter().filter(|todo| filter.matches(todo)).map(|todo| todo.id).collect();
+        assert_eq!(matched, expected, "{}", input);
+    }
+}
@@ -0,0 +1,48 @@
+//! Tests for the iCalendar import and export: what a `VTODO` keeps, and
+//! which stored task an imported one is matched with.
+
+use cli_todo_rust::{ical, Priority, Status, Timestamp, Todo};
+
+fn time(s: &str) -> Option<Timestamp> {
+    Some(s.parse().unwrap())
+}
+
+#[test]
+fn todos_round_trip() {
+    let mut full = Todo::new("Plan offsite; book a room, then tell everyone");
+    full.id = 7;
+    full.priority = Some(Priority::High);
+    full.due = Some("2026-11-01".parse().unwrap());
+    full.tags = vec!["work".to_string(), "q4".to_string()];
+    full.project = Some("team".to_string());
+    full.created_at = time("2026-10-01T08:00:00Z");
+    full.updated_at = time("2026-10-15T09:30:00Z");
+
+    for status in Status::ALL {
+        let mut todo = full.clone();
+        todo.status = status;
+        if status == Status::Done {
+            todo.completed_at = todo.updated_at;
+        }
+        let text = ical::format(&[todo.clone()]);
+        let back = ical::parse(&text).unwrap().remove(0);
+        assert_eq!(back.extra["uid"], ical::uid(&todo).as_str());
+        assert_eq!(ical::merge(&todo, &back), todo, "through\n{}", text);
+    }
+}
+
+#[test]
+fn uids_tell_lists_apart() {
+    // Task 1 in two different lists
+    let work = Todo { id: 1, created_at: time("2026-10-01T08:00:00Z"), ..Todo::new("work thing") };
+    let home = Todo { id: 1, created_at: time("2026-10-03T19:15:00Z"), ..Todo::new("home thing") };
+
+    let imported = ical::parse(&ical::format(std::slice::from_ref(&work))).unwrap().remove(0);
+    assert_ne!(ical::uid(&imported), ical::uid(&home));
+    // Imported into the list it came from, it still finds its task
+    assert_eq!(ical::uid(&imported), ical::uid(&work));
+
+    // Exported again from another list, it keeps the UID it came with
+    let copy = Todo { id: 4, ..imported.clone() };
+    assert_eq!(ical::uid(&copy), ical::uid(&work));
+}
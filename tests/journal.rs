@@ -0,0 +1,134 @@
+//! Tests for undo and redo: walking the journal back and forth across
+//! saves, and refusing to replay over changes the journal didn't record.
+
+use std::path::{Path, PathBuf};
+
+use cli_todo_rust::journal::Journal;
+use cli_todo_rust::store::{self, Backend};
+use cli_todo_rust::{Error, Status, TodoList};
+
+mod common;
+use common::TempDir;
+
+/// A fresh directory, and the list's path inside it.
+fn temp_list(name: &str) -> (TempDir, PathBuf) {
+    let dir = TempDir::new(name);
+    let path = dir.join("todos.json");
+    (dir, path)
+}
+
+fn open(path: &Path) -> TodoList {
+    TodoList::new(store::open(Backend::Json, path, true).unwrap()).with_journal(Journal::path_for(path))
+}
+
+/// Each todo as `id description status`, to compare lists at a glance.
+fn contents(path: &Path) -> Vec<String> {
+    let list = open(path);
+    list.all().unwrap().iter().map(|todo| format!("{} {} {}", todo.id, todo.description, todo.status)).collect()
+}
+
+fn summaries(path: &Path) -> Vec<(String, bool)> {
+    let journal = open(path).history().unwrap();
+    journal.entries().map(|(entry, applied)| (entry.summary(), applied)).collect()
+}
+
+#[test]
+fn undo_and_redo_walk_the_journal() {
+    let (_dir, path) = temp_list("walk");
+    let mut list = open(&path);
+    list.add("Pay rent").unwrap();
+    list.save().unwrap();
+    list.add("Call mom").unwrap();
+    list.complete(1).unwrap();
+    list.save().unwrap();
+    drop(list);
+    assert_eq!(summaries(&path), [("add 1 (Pay rent)".to_string(), true), ("change 2 tasks (2, 1)".to_string(), true)]);
+
+    // Each undo is saved on its own, in a later run
+    let mut list = open(&path);
+    assert_eq!(list.undo().unwrap().unwrap().summary(), "change 2 tasks (2, 1)");
+    list.save().unwrap();
+    drop(list);
+    assert_eq!(contents(&path), ["1 Pay rent todo"]);
+    assert_eq!(summaries(&path)[1], ("change 2 tasks (2, 1)".to_string(), false));
+
+    let mut list = open(&path);
+    assert_eq!(list.undo().unwrap().unwrap().summary(), "add 1 (Pay rent)");
+    list.save().unwrap();
+    assert!(list.undo().unwrap().is_none());
+    drop(list);
+    assert!(contents(&path).is_empty());
+
+    let mut list = open(&path);
+    list.redo().unwrap().unwrap();
+    list.save().unwrap();
+    list.redo().unwrap().unwrap();
+    list.save().unwrap();
+    assert!(list.redo().unwrap().is_none());
+    drop(list);
+    assert_eq!(contents(&path), ["1 Pay rent done", "2 Call mom todo"]);
+
+    // A new change after an undo drops what could have been redone
+    let mut list = open(&path);
+    list.undo().unwrap();
+    list.save().unwrap();
+    list.set_status(1, Status::Cancelled).unwrap();
+    list.save().unwrap();
+    assert!(list.redo().unwrap().is_none());
+    drop(list);
+    assert_eq!(summaries(&path), [("add 1 (Pay rent)".to_string(), true), ("cancel 1 (Pay rent)".to_string(), true)]);
+}
+
+#[test]
+fn replays_over_unrecorded_changes_are_refused() {
+    let (_dir, path) = temp_list("conflicts");
+    let mut list = open(&path);
+    list.add("Pay rent").unwrap();
+    list.add("Call mom").unwrap();
+    list.save().unwrap();
+
+    // Undo and redo only work on saved changes
+    list.complete(2).unwrap();
+    let error = list.undo().unwrap_err();
+    assert!(matches!(error, Error::Validation(_)), "{}", error);
+    assert_eq!(error.to_string(), "save the list before undoing or redoing");
+    list.save().unwrap();
+    list.undo().unwrap();
+    assert_eq!(list.redo().unwrap_err().to_string(), "save the list before undoing or redoing");
+    list.save().unwrap();
+    drop(list);
+
+    // A change that bypasses the journal
+    let mut other = TodoList::new(store::open(Backend::Json, &path, true).unwrap());
+    let mut todo = other.get(1).unwrap();
+    todo.description = "Pay the rent".to_string();
+    other.edit(&todo).unwrap();
+    other.save().unwrap();
+    drop(other);
+
+    let mut list = open(&path);
+    let error = list.undo().unwrap_err();
+    assert!(matches!(error, Error::Validation(_)), "{}", error);
+    assert_eq!(error.to_string(), "task 1 was changed outside of the journal since then, so this can't be undone");
+    // Task 2 can still be redone, as it wasn't touched
+    list.redo().unwrap().unwrap();
+    list.save().unwrap();
+    drop(list);
+    assert_eq!(contents(&path), ["1 Pay the rent todo", "2 Call mom done"]);
+
+    let mut other = TodoList::new(store::open(Backend::Json, &path, true).unwrap());
+    other.remove(2).unwrap();
+    other.save().unwrap();
+    drop(other);
+
+    let mut list = open(&path);
+    assert_eq!(
+        list.undo().unwrap_err().to_string(),
+        "task 2 was changed outside of the journal since then, so this can't be undone"
+    );
+    // Nothing was changed by the refused undo
+    list.save().unwrap();
+    drop(list);
+    assert_eq!(contents(&path), ["1 Pay the rent todo"]);
+    assert_eq!(summaries(&path).iter().filter(|(_, applied)| *applied).count(), 2);
+}
@@ -0,0 +1,141 @@
+//! Tests for the JSON backend's file handling: saving in place of the old
+//! file without changing who may read it, and `doctor` salvaging what it can
+//! from a file that no longer loads.
+
+use std::fs;
+use std::path::Path;
+
+use cli_todo_rust::store::{self, repair_file, Backend};
+use cli_todo_rust::{Error, Status, Todo};
+
+mod common;
+use common::TempDir;
+
+#[cfg(unix)]
+#[test]
+fn saving_keeps_the_file_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new("permissions");
+    let path = dir.join("todos.json");
+    let mode = |file: &str| fs::metadata(dir.join(file)).unwrap().permissions().mode() & 0o777;
+    let mut store = store::open(Backend::Json, &path, true).unwrap();
+    store.insert(Todo::new("Pay rent")).unwrap();
+    store.commit().unwrap();
+    drop(store);
+
+    for private in [0o600, 0o640, 0o400] {
+        fs::set_permissions(&path, fs::Permissions::from_mode(private)).unwrap();
+        let mut store = store::open(Backend::Json, &path, true).unwrap();
+        store.insert(Todo::new("Call mom")).unwrap();
+        store.commit().unwrap();
+        drop(store);
+        assert_eq!(mode("todos.json"), private);
+        assert_eq!(mode("todos.json.bak"), private);
+    }
+}
+
+/// A list saved with `descriptions`, and the file's text.
+fn saved(path: &Path, descriptions: &[&str]) -> String {
+    let mut store = store::open(Backend::Json, path, true).unwrap();
+    for description in descriptions {
+        store.insert(Todo::new(*description)).unwrap();
+    }
+    store.commit().unwrap();
+    drop(store);
+    fs::read_to_string(path).unwrap()
+}
+
+/// The IDs and descriptions `doctor` left in the repaired file.
+fn repaired(path: &Path) -> Vec<(u64, String)> {
+    let store = store::open(Backend::Json, path, false).unwrap();
+    store.load().unwrap().into_iter().map(|todo| (todo.id, todo.description)).collect()
+}
+
+/// The contents of every damaged file moved aside from next to `path`.
+fn quarantined(path: &Path) -> Vec<String> {
+    let files = fs::read_dir(path.parent().unwrap()).unwrap().map(|entry| entry.unwrap().path());
+    files.filter(|path| path.to_string_lossy().contains(".corrupt-"))
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect()
+}
+
+#[test]
+fn a_truncated_file_keeps_its_whole_entries() {
+    let dir = TempDir::new("truncated");
+    let path = dir.join("todos.json");
+    assert!(repair_file(&path).unwrap().contains("nothing to check"));
+    let complete = saved(&path, &["Pay rent", "Call mom", "Water plants"]);
+    assert!(repair_file(&path).unwrap().contains("is healthy (3 tasks)"));
+
+    // Cut off in the middle of the last task
+    let truncated = &complete[..complete.find("Water plants").unwrap()];
+    fs::write(&path, truncated).unwrap();
+    let report = repair_file(&path).unwrap();
+    assert!(report.contains("Recovered 2 task(s)"), "{}", report);
+    assert_eq!(repaired(&path), [(1, "Pay rent".to_string()), (2, "Call mom".to_string())]);
+    assert_eq!(quarantined(&path), [truncated]);
+
+    // The lost task's ID isn't handed out again
+    let mut store = store::open(Backend::Json, &path, true).unwrap();
+    assert_eq!(store.insert(Todo::new("Sweep")).unwrap().id, 4);
+}
+
+#[test]
+fn a_truncated_array_of_the_original_format_is_numbered() {
+    let dir = TempDir::new("truncated-v0");
+    let path = dir.join("todos.json");
+    let truncated = r#"[
+  {"description": "Pay rent", "completed": true},
+  {"description": "Call mom", "completed": false},
+  {"description": "Water pl"#;
+    fs::write(&path, truncated).unwrap();
+    assert!(repair_file(&path).unwrap().contains("Recovered 2 task(s)"));
+
+    let store = store::open(Backend::Json, &path, false).unwrap();
+    let todos: Vec<_> = store.load().unwrap().into_iter().map(|todo| (todo.id, todo.description, todo.status)).collect();
+    assert_eq!(todos, [(1, "Pay rent".to_string(), Status::Done), (2, "Call mom".to_string(), Status::Todo)]);
+    assert_eq!(quarantined(&path), [truncated]);
+}
+
+#[test]
+fn braces_inside_descriptions_are_not_entries() {
+    let dir = TempDir::new("braces");
+    let path = dir.join("todos.json");
+    let tricky = ["Fix {weird} config }", "Quote \"}{\" in a \\ path {", "Call mom"];
+    let complete = saved(&path, &tricky);
+    let truncated = &complete[..complete.rfind("Call mom").unwrap()];
+    fs::write(&path, truncated).unwrap();
+    repair_file(&path).unwrap();
+    assert_eq!(repaired(&path), [(1, tricky[0].to_string()), (2, tricky[1].to_string())]);
+}
+
+#[test]
+fn duplicate_ids_get_fresh_ones() {
+    let dir = TempDir::new("duplicates");
+    let path = dir.join("todos.json");
+    let complete = saved(&path, &["Pay rent", "Call mom", "Water plants"]);
+    // A bad hand edit: task 2 renumbered to 1, and the closing brackets lost
+    let damaged = complete.replacen("\"id\": 2", "\"id\": 1", 1);
+    let damaged = damaged.trim_end().trim_end_matches('}').trim_end().trim_end_matches(']');
+    fs::write(&path, damaged).unwrap();
+    assert!(repair_file(&path).unwrap().contains("Recovered 3 task(s)"));
+
+    // The first keeps it; the other gets the next unused ID
+    let expected = [(1, "Pay rent".to_string()), (4, "Call mom".to_string()), (3, "Water plants".to_string())];
+    assert_eq!(repaired(&path), expected);
+}
+
+#[test]
+fn files_from_a_newer_version_are_left_alone() {
+    let dir = TempDir::new("newer");
+    let path = dir.join("todos.json");
+    let newer = r#"{"version": 1000, "next_id": 3, "todos": [{"id": 1, "description": "Pay rent", "#;
+    fs::write(&path, newer).unwrap();
+
+    let error = repair_file(&path).unwrap_err();
+    assert!(matches!(error, Error::Parse(_)), "{}", error);
+    assert!(error.to_string().contains("newer version"), "{}", error);
+    assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+    assert!(quarantined(&path).is_empty());
+}
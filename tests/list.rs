@@ -0,0 +1,121 @@
+//! Tests for picking the tasks a command works on: ID and position lists,
+//! ranges over removed IDs, and positions past the end of the list.
+
+use cli_todo_rust::store::{self, Backend};
+use cli_todo_rust::{Error, NumberList, Selection, Todo, TodoList};
+
+mod common;
+use common::TempDir;
+
+/// A fresh JSON-backed list holding tasks 1 to `count`.
+fn temp_list(name: &str, count: u64) -> (TempDir, TodoList) {
+    let dir = TempDir::new(name);
+    let mut list = TodoList::new(store::open(Backend::Json, &dir.join("todos.json"), true).unwrap());
+    for i in 1..=count {
+        list.add_todo(Todo::new(format!("Task {}", i))).unwrap();
+    }
+    list.save().unwrap();
+    (dir, list)
+}
+
+fn ids(list: &str) -> Selection {
+    Selection::Ids(list.parse().unwrap())
+}
+
+fn positions(list: &str) -> Selection {
+    Selection::Positions(list.parse().unwrap())
+}
+
+fn remaining(list: &TodoList) -> Vec<u64> {
+    list.all().unwrap().iter().map(|todo| todo.id).collect()
+}
+
+#[test]
+fn number_lists_parse_and_backwards_ranges_are_refused() {
+    assert_eq!("3".parse::<NumberList>().unwrap(), NumberList(vec![3..=3]));
+    assert_eq!(" 1, 3 ,5-9,4-4".parse::<NumberList>().unwrap(), NumberList(vec![1..=1, 3..=3, 5..=9, 4..=4]));
+
+    let invalid = [
+        ("5-2", "the range 5-2 is backwards"),
+        ("1,9-3", "the range 9-3 is backwards"),
+        ("", "`` is not a number"),
+        ("1,,2", "`` is not a number"),
+        ("1-", "`` is not a number"),
+        ("-1", "`` is not a number"),
+        ("two", "`two` is not a number"),
+        ("1-2-3", "`2-3` is not a number"),
+    ];
+    for (text, message) in invalid {
+        let error = text.parse::<NumberList>().unwrap_err();
+        assert!(matches!(error, Error::Validation(_)), "{}", error);
+        assert_eq!(error.to_string(), message, "{}", text);
+    }
+}
+
+#[test]
+fn positions_are_resolved_before_anything_is_removed() {
+    let (_dir, mut list) = temp_list("remove-index", 4);
+    // What `remove --index 0,1` does: the second task is still at position 1
+    for id in list.resolve(&positions("0,1")).unwrap() {
+        list.remove(id).unwrap();
+    }
+    list.save().unwrap();
+    assert_eq!(remaining(&list), [3, 4]);
+
+    assert_eq!(list.resolve(&positions("1,0-1")).unwrap(), [4, 3]);
+}
+
+#[test]
+fn id_ranges_skip_removed_ids() {
+    let (_dir, mut list) = temp_list("gaps", 6);
+    for id in [2, 3, 5] {
+        list.remove(id).unwrap();
+    }
+    list.save().unwrap();
+
+    assert_eq!(list.resolve(&ids("1-6")).unwrap(), [1, 4, 6]);
+    // In the order given, each task once
+    assert_eq!(list.resolve(&ids("6,1-4,4")).unwrap(), [6, 1, 4]);
+    // A range only fails if it holds no task at all; a single ID always must exist
+    let missing = [
+        ("2-3", "with an ID in 2-3"),
+        ("1,2-3", "with an ID in 2-3"),
+        ("7-9", "with an ID in 7-9"),
+        ("2", "2"),
+        ("4,5", "5"),
+    ];
+    for (text, task) in missing {
+        let error = list.resolve(&ids(text)).unwrap_err();
+        assert!(matches!(&error, Error::NotFound(found) if found == task), "{}: {}", text, error);
+    }
+}
+
+#[test]
+fn positions_past_the_end_are_not_found() {
+    let (_dir, list) = temp_list("out-of-range", 3);
+    assert_eq!(list.resolve(&positions("2")).unwrap(), [3]);
+    let past_the_end = [("3", "3"), ("0,1-5", "5"), ("18446744073709551615", "18446744073709551615")];
+    for (text, position) in past_the_end {
+        let position = format!("at position {}", position);
+        let error = list.resolve(&positions(text)).unwrap_err();
+        assert!(matches!(&error, Error::NotFound(found) if *found == position), "{}: {}", text, error);
+    }
+
+    let (_dir, empty) = temp_list("empty", 0);
+    assert!(matches!(empty.resolve(&positions("0")), Err(Error::NotFound(_))));
+    assert!(matches!(empty.resolve(&ids("1-9")), Err(Error::NotFound(_))));
+}
+
+#[test]
+fn filters_select_every_match() {
+    let (_dir, mut list) = temp_list("filter", 3);
+    let mut todo = list.get(2).unwrap();
+    todo.tags = vec!["work".to_string()];
+    list.edit(&todo).unwrap();
+    list.save().unwrap();
+
+    assert_eq!(list.resolve(&Selection::Matching("tag:work or id:3".to_string())).unwrap(), [2, 3]);
+    let error = list.resolve(&Selection::Matching("tag:home".to_string())).unwrap_err();
+    assert_eq!(error.to_string(), "Task matching `tag:home` doesn't exist");
+    assert!(matches!(list.resolve(&Selection::Matching("(tag:work".to_string())), Err(Error::Validation(_))));
+}
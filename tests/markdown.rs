@@ -0,0 +1,151 @@
+//! Tests for Markdown task lists: nesting, links, and two-way sync.
+
+use cli_todo_rust::markdown;
+use cli_todo_rust::store::{self, Backend};
+use cli_todo_rust::{Status, Timestamp, Todo, TodoList};
+
+mod common;
+use common::TempDir;
+
+/// A fresh JSON-backed list in its own directory.
+fn temp_list(name: &str) -> (TempDir, TodoList) {
+    let dir = TempDir::new(name);
+    let list = TodoList::new(store::open(Backend::Json, &dir.join("todos.json"), true).unwrap());
+    (dir, list)
+}
+
+#[test]
+fn items_are_read_with_their_nesting() {
+    let text = "# Plan\n\
+                - [ ] Offsite @work <!-- id:7 status:todo -->\n\
+                \x20 - [X] Venue +travel\n\
+                \x20   1. [~] Deposit\n\
+                \x20 - [-] Dropped\n\
+                * Notes\n\
+                \x20 - [!] Under notes\n\
+                ```\n\
+                - [ ] in a code block\n\
+                ```\n\
+                - [ ] Last\n";
+    let items = markdown::parse(text).unwrap();
+    let summary: Vec<_> = items
+        .iter()
+        .map(|item| (item.line, item.todo.description.as_str(), item.todo.status, item.todo.parent))
+        .collect();
+    assert_eq!(summary, [
+        (1, "Offsite", Status::Todo, None),
+        (2, "Venue", Status::Done, Some(1)),
+        (3, "Deposit", Status::InProgress, Some(2)),
+        (4, "Dropped", Status::Cancelled, Some(1)),
+        (6, "Under notes", Status::Blocked, None),
+        (10, "Last", Status::Todo, None),
+    ]);
+    assert_eq!(items[0].todo.project.as_deref(), Some("work"));
+    assert_eq!(items[1].todo.tags, ["travel"]);
+    assert_eq!(items[0].link, Some(markdown::Link { id: 7, status: Some(Status::Todo) }));
+    assert_eq!(items[1].link, None);
+
+    // Other `@` words, like GitHub mentions, stay in the description
+    let items = markdown::parse("- [ ] ping @alice and @bob +team @work\n- [ ] ask @carol\n").unwrap();
+    let todo = &items[0].todo;
+    assert_eq!(todo.description, "ping @alice and @bob");
+    assert_eq!((todo.project.as_deref(), &todo.tags[..]), (Some("work"), &["team".to_string()][..]));
+    assert_eq!((items[1].todo.description.as_str(), items[1].todo.project.as_deref()), ("ask", Some("carol")));
+    let back = markdown::parse(&markdown::format(std::slice::from_ref(todo))).unwrap();
+    assert_eq!(back[0].todo.description, todo.description);
+    assert_eq!(back[0].todo.project, todo.project);
+
+    let error = markdown::parse("- [ ] Fine\n- [ ] +tag-only\n").unwrap_err();
+    assert_eq!(error.to_string(), "line 2: the description must not be empty");
+}
+
+#[test]
+fn exports_read_back_as_the_same_tree() {
+    let mut parent = Todo::new("Offsite");
+    parent.id = 1;
+    parent.project = Some("work".to_string());
+    let child = Todo { id: 2, parent: Some(1), status: Status::Done, ..Todo::new("Venue") };
+    let grandchild = Todo { id: 5, parent: Some(2), ..Todo::new("Deposit") };
+    let orphan = Todo { id: 9, parent: Some(4), status: Status::Blocked, ..Todo::new("Orphan") };
+
+    let text = markdown::format(&[parent, child, grandchild, orphan]);
+    assert_eq!(
+        text,
+        "- [ ] Offsite @work <!-- id:1 status:todo -->\n\
+         \x20 - [x] Venue <!-- id:2 status:done -->\n\
+         \x20   - [ ] Deposit <!-- id:5 status:todo -->\n\
+         - [!] Orphan <!-- id:9 status:blocked -->\n"
+    );
+    let items = markdown::parse(&text).unwrap();
+    let links: Vec<_> = items.iter().map(|item| item.link.unwrap().id).collect();
+    assert_eq!(links, [1, 2, 5, 9]);
+    let parents: Vec<_> = items.iter().map(|item| item.todo.parent).collect();
+    assert_eq!(parents, [None, Some(1), Some(2), None]);
+}
+
+#[test]
+fn nested_items_import_into_a_list_with_tasks() {
+    let (_dir, mut list) = temp_list("import");
+    list.add("Already there").unwrap();
+    list.add("Also there").unwrap();
+
+    // Items name their parents by position, which are stored IDs here
+    let mut ids = Vec::new();
+    for item in markdown::parse("- [ ] A\n  - [ ] B\n    - [x] C\n").unwrap() {
+        let parent = item.todo.parent.map(|position| ids[position as usize - 1]);
+        ids.push(list.import(Todo { parent, ..item.todo }).unwrap().id);
+    }
+    assert_eq!(ids, [3, 4, 5]);
+    let parents: Vec<_> = ids.iter().map(|&id| list.get(id).unwrap().parent).collect();
+    assert_eq!(parents, [None, Some(3), Some(4)]);
+}
+
+#[test]
+fn sync_goes_both_ways() {
+    let (_dir, mut list) = temp_list("sync");
+    let kept = list.add("Changed in the file").unwrap();
+    let done = list.add("Changed in the list").unwrap();
+    let both = list.add("Changed on both sides").unwrap();
+    list.complete(done.id).unwrap();
+    list.set_status(both.id, Status::Blocked).unwrap();
+    let before = Timestamp::from_unix(0);
+    let after = Timestamp::from_unix(i64::MAX / 2);
+
+    let text = format!(
+        "- [x] Changed in the file <!-- id:{} status:todo -->\r\n\
+         - [ ] Changed in the list <!-- id:{} status:todo -->\r\n\
+         - [-] Changed on both sides <!-- id:{} status:todo -->\r\n\
+         \x20 - [ ] New subtask\r\n\
+         - [ ] Removed <!-- id:99 -->\r\n",
+        kept.id, done.id, both.id
+    );
+    // The file is older than the list's changes, so the list wins the conflict
+    let synced = markdown::sync(&mut list, &text, before).unwrap();
+    assert_eq!(list.get(kept.id).unwrap().status, Status::Done);
+    assert_eq!(list.get(both.id).unwrap().status, Status::Blocked);
+    let new = &synced.added[0];
+    assert_eq!((new.description.as_str(), new.parent), ("New subtask", Some(both.id)));
+    assert_eq!(synced.missing, [99]);
+    assert_eq!(
+        synced.text,
+        format!(
+            "- [x] Changed in the file <!-- id:{} status:done -->\r\n\
+             - [x] Changed in the list <!-- id:{} status:done -->\r\n\
+             - [!] Changed on both sides <!-- id:{} status:blocked -->\r\n\
+             \x20 - [ ] New subtask <!-- id:{} status:todo -->\r\n\
+             - [ ] Removed <!-- id:99 -->\r\n",
+            kept.id, done.id, both.id, new.id
+        )
+    );
+
+    // Synced again, nothing changes
+    let again = markdown::sync(&mut list, &synced.text, before).unwrap();
+    assert!(again.added.is_empty() && again.updated.is_empty() && again.written.is_empty());
+    assert_eq!(again.text, synced.text);
+
+    // A newer file wins the conflict
+    let synced = markdown::sync(&mut list, &text, after).unwrap();
+    assert_eq!(list.get(both.id).unwrap().status, Status::Cancelled);
+    let updated: Vec<_> = synced.updated.iter().map(|todo| todo.id).collect();
+    assert_eq!(updated, [both.id]);
+}
@@ -0,0 +1,119 @@
+//! Tests for the JSON file's schema migrations: a file written by any older
+//! version loads as the same list, and one from a newer version is refused.
+
+use std::fs;
+use std::path::Path;
+
+use cli_todo_rust::store::{self, Backend};
+use cli_todo_rust::{Error, Status, Todo};
+use serde_json::{json, Value};
+
+mod common;
+use common::TempDir;
+
+fn read_json(path: &Path) -> Value {
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+/// The same two todos, "Pay rent" (done) and "Call mom", as a document of
+/// schema `version`, with the ID counter at 5 where there is one.
+fn document(version: u64) -> Value {
+    let todo = |id: u64, description: &str, done: bool| {
+        let mut todo = json!({ "description": description });
+        if version >= 1 {
+            todo["id"] = json!(id);
+        }
+        if version < 3 {
+            todo["completed"] = json!(done);
+        } else {
+            todo["status"] = json!(if done { "done" } else { "todo" });
+        }
+        // The fields each version added, still empty
+        let added: [(u64, &[&str]); 5] = [
+            (4, &["due"]),
+            (5, &["priority"]),
+            (6, &["project"]),
+            (7, &["created_at", "updated_at", "completed_at"]),
+            (9, &["parent"]),
+        ];
+        for (_, fields) in added.into_iter().filter(|&(since, _)| version >= since) {
+            for field in fields {
+                todo[*field] = Value::Null;
+            }
+        }
+        if version >= 6 {
+            todo["tags"] = json!([]);
+        }
+        if version >= 8 {
+            todo["extra"] = json!({});
+        }
+        todo
+    };
+    let todos = json!([todo(1, "Pay rent", true), todo(2, "Call mom", false)]);
+    match version {
+        0 => todos,
+        1 => json!({ "next_id": 5, "todos": todos }),
+        _ => json!({ "version": version, "next_id": 5, "todos": todos }),
+    }
+}
+
+/// The schema version this build writes, as found in a new file.
+fn current_version(dir: &TempDir) -> u64 {
+    let path = dir.join("new.json");
+    drop(store::open(Backend::Json, &path, true).unwrap());
+    read_json(&path)["version"].as_u64().unwrap()
+}
+
+#[test]
+fn every_old_version_loads_as_the_same_list() {
+    let dir = TempDir::new("versions");
+    let current = current_version(&dir);
+    let expected = vec![
+        Todo { id: 1, status: Status::Done, ..Todo::new("Pay rent") },
+        Todo { id: 2, ..Todo::new("Call mom") },
+    ];
+
+    for version in 0..=current {
+        let path = dir.join(format!("v{}.json", version));
+        let text = serde_json::to_string_pretty(&document(version)).unwrap();
+        fs::write(&path, &text).unwrap();
+
+        let mut store = store::open(Backend::Json, &path, true).unwrap();
+        assert_eq!(store.load().unwrap(), expected, "version {}", version);
+        // Loading alone doesn't rewrite the file
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+
+        // The ID counter carries over; the bare array of version 0 had none
+        let added = store.insert(Todo::new("New")).unwrap();
+        assert_eq!(added.id, if version == 0 { 3 } else { 5 }, "version {}", version);
+        store.commit().unwrap();
+        drop(store);
+
+        // Saving writes the current version and keeps the old file
+        assert_eq!(read_json(&path)["version"], json!(current));
+        assert_eq!(fs::read_to_string(dir.join(format!("v{}.json.bak", version))).unwrap(), text);
+    }
+}
+
+#[test]
+fn newer_and_malformed_files_are_refused() {
+    let dir = TempDir::new("refused");
+    let current = current_version(&dir);
+    let newer = format!("{{\"version\": {}, \"next_id\": 1, \"todos\": []}}", current + 1);
+    let documents = [
+        (newer.as_str(), "please upgrade"),
+        ("[1, 2]", "expected every todo to be an object"),
+        ("{\"version\": 2, \"next_id\": 1, \"todos\": {}}", "expected a `todos` array"),
+        ("{\"version\": \"two\", \"todos\": []}", "invalid schema version"),
+        ("\"todos\"", "expected a JSON object or array"),
+    ];
+
+    for (i, (text, message)) in documents.into_iter().enumerate() {
+        let path = dir.join(format!("{}.json", i));
+        fs::write(&path, text).unwrap();
+        let error = store::open(Backend::Json, &path, true).err().unwrap();
+        assert!(matches!(error, Error::Parse(_)), "{}", error);
+        assert!(error.to_string().contains(message), "{}", error);
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+    }
+}
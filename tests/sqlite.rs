@@ -0,0 +1,186 @@
+//! Tests for the SQLite backend: round trips, upgrading older databases,
+//! single-row lookups, restoring deleted rows, and rolling back uncommitted
+//! changes. Only built with `--features sqlite`.
+#![cfg(feature = "sqlite")]
+
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use cli_todo_rust::store::{self, Backend, TodoStore};
+use cli_todo_rust::{Date, Error, Priority, Status, Todo};
+
+mod common;
+use common::TempDir;
+
+#[link(name = "sqlite3")]
+extern "C" {
+    fn sqlite3_open(filename: *const c_char, db: *mut *mut c_void) -> c_int;
+    fn sqlite3_exec(
+        db: *mut c_void,
+        sql: *const c_char,
+        callback: *const c_void,
+        arg: *mut c_void,
+        errmsg: *mut *mut c_char,
+    ) -> c_int;
+    fn sqlite3_close(db: *mut c_void) -> c_int;
+}
+
+/// Run `sql` on the database at `path` directly, as an older build (or
+/// another program) would.
+fn execute(path: &Path, sql: &str) {
+    let name = CString::new(path.to_str().unwrap()).unwrap();
+    let sql = CString::new(sql).unwrap();
+    let mut db = ptr::null_mut();
+    // SAFETY: both strings are valid C strings and `db` is closed below.
+    unsafe {
+        assert_eq!(sqlite3_open(name.as_ptr(), &mut db), 0);
+        let rc = sqlite3_exec(db, sql.as_ptr(), ptr::null(), ptr::null_mut(), ptr::null_mut());
+        sqlite3_close(db);
+        assert_eq!(rc, 0, "{}", sql.to_str().unwrap());
+    }
+}
+
+/// A fresh directory, and the database's path inside it.
+fn temp_db(name: &str) -> (TempDir, PathBuf) {
+    let dir = TempDir::new(name);
+    let path = dir.join("todos.db");
+    (dir, path)
+}
+
+fn open(path: &Path, exclusive: bool) -> Box<dyn TodoStore> {
+    store::open(Backend::Sqlite, path, exclusive).unwrap()
+}
+
+#[test]
+fn todos_round_trip() {
+    let (_dir, path) = temp_db("round-trip");
+    let mut store = open(&path, true);
+    let rent = Todo {
+        status: Status::InProgress,
+        due: Some("2026-11-01".parse::<Date>().unwrap()),
+        priority: Some(Priority::High),
+        tags: vec!["home".to_string(), "money".to_string()],
+        project: Some("flat".to_string()),
+        ..Todo::new("Pay rent \"on time\" ✓")
+    };
+    let rent = store.insert(rent).unwrap();
+    let mut call = Todo { parent: Some(rent.id), ..Todo::new("Call the landlord") };
+    call.extra.insert("uda".to_string(), serde_json::json!({"nested": [1, 2]}));
+    let call = store.insert(call).unwrap();
+    let water = store.insert(Todo::new("Water plants")).unwrap();
+    assert_eq!((rent.id, call.id, water.id), (1, 2, 3));
+    store.commit().unwrap();
+    drop(store);
+
+    let store = open(&path, false);
+    assert_eq!(store.load().unwrap(), [rent.clone(), call.clone(), water]);
+    assert_eq!(store.get(2).unwrap(), Some(call.clone()));
+    assert_eq!(store.get(4).unwrap(), None);
+    assert_eq!(store.subtasks(rent.id).unwrap(), [call]);
+    assert!(store.subtasks(3).unwrap().is_empty());
+    assert_eq!(store.query(&|todo| todo.tags.contains(&"money".to_string())).unwrap(), [rent]);
+}
+
+#[test]
+fn readers_share_the_database_without_writing_it() {
+    let (_dir, path) = temp_db("readers");
+    let mut store = open(&path, true);
+    store.insert(Todo::new("Pay rent")).unwrap();
+    store.commit().unwrap();
+    drop(store);
+    let before = fs::read(&path).unwrap();
+
+    let readers: Vec<_> = (0..4).map(|_| open(&path, false)).collect();
+    for reader in &readers {
+        assert_eq!(reader.load().unwrap().len(), 1);
+    }
+    drop(readers);
+    assert_eq!(fs::read(&path).unwrap(), before);
+}
+
+#[test]
+fn older_databases_are_upgraded_and_newer_ones_refused() {
+    let (dir, path) = temp_db("upgrade");
+    // Schema 5 had `status` and `priority`, but no tags or timestamps
+    execute(
+        &path,
+        r#"CREATE TABLE todos (id INTEGER PRIMARY KEY AUTOINCREMENT, todo TEXT NOT NULL);
+           INSERT INTO todos (id, todo) VALUES (1, '{"description": "Pay rent", "status": "done", "priority": "H"}');
+           INSERT INTO todos (id, todo) VALUES (4, '{"description": "Call mom", "status": "todo", "due": "2026-10-20"}');
+           PRAGMA user_version = 5;"#,
+    );
+    let expected = [
+        Todo { id: 1, status: Status::Done, priority: Some(Priority::High), ..Todo::new("Pay rent") },
+        Todo { id: 4, due: Some("2026-10-20".parse().unwrap()), ..Todo::new("Call mom") },
+    ];
+
+    // Even a reader can upgrade it, and the next reader finds it done
+    assert_eq!(open(&path, false).load().unwrap(), expected);
+    let upgraded = fs::read(&path).unwrap();
+    let store = open(&path, false);
+    assert_eq!(store.load().unwrap(), expected);
+    assert_eq!(store.subtasks(1).unwrap(), []);
+    drop(store);
+    assert_eq!(fs::read(&path).unwrap(), upgraded);
+
+    // IDs carry on after the highest one ever handed out
+    let mut store = open(&path, true);
+    assert_eq!(store.insert(Todo::new("New")).unwrap().id, 5);
+    drop(store);
+
+    let newer = dir.join("newer.db");
+    execute(&newer, "CREATE TABLE todos (id INTEGER PRIMARY KEY, todo TEXT NOT NULL); PRAGMA user_version = 1000;");
+    for exclusive in [false, true] {
+        let error = store::open(Backend::Sqlite, &newer, exclusive).err().unwrap();
+        assert!(matches!(error, Error::Parse(_)), "{}", error);
+        assert!(error.to_string().contains("please upgrade"), "{}", error);
+    }
+}
+
+#[test]
+fn deleted_todos_can_be_restored_under_their_id() {
+    let (_dir, path) = temp_db("restore");
+    let mut store = open(&path, true);
+    for description in ["Pay rent", "Call mom", "Water plants"] {
+        store.insert(Todo::new(description)).unwrap();
+    }
+    let mom = store.delete(2).unwrap();
+    let plants = store.delete(3).unwrap();
+    assert!(matches!(store.delete(3), Err(Error::NotFound(_))));
+    store.commit().unwrap();
+
+    store.restore(&mom).unwrap();
+    let error = store.restore(&mom).unwrap_err();
+    assert_eq!(error.to_string(), "task 2 already exists");
+    assert!(matches!(store.update(&plants), Err(Error::NotFound(_))));
+    // Deleted IDs are never handed out again
+    assert_eq!(store.insert(Todo::new("Sweep")).unwrap().id, 4);
+    store.commit().unwrap();
+    drop(store);
+
+    let ids: Vec<u64> = open(&path, false).load().unwrap().iter().map(|todo| todo.id).collect();
+    assert_eq!(ids, [1, 2, 4]);
+}
+
+#[test]
+fn uncommitted_changes_are_rolled_back_on_drop() {
+    let (_dir, path) = temp_db("rollback");
+    let mut store = open(&path, true);
+    let rent = store.insert(Todo::new("Pay rent")).unwrap();
+    store.commit().unwrap();
+
+    let mut changed = rent.clone();
+    changed.status = Status::Done;
+    store.update(&changed).unwrap();
+    store.insert(Todo::new("Call mom")).unwrap();
+    assert_eq!(store.load().unwrap().len(), 2);
+    drop(store);
+
+    let mut store = open(&path, true);
+    assert_eq!(store.load().unwrap(), std::slice::from_ref(&rent));
+    store.delete(rent.id).unwrap();
+    drop(store);
+    assert_eq!(open(&path, false).load().unwrap(), [rent]);
+}
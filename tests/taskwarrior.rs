@@ -0,0 +1,132 @@
+//! Round-trip tests for the Taskwarrior import and export: whatever a task
+//! comes with must survive being read and written again.
+
+use cli_todo_rust::{taskwarrior, Date, Priority, Status, Timestamp, Todo};
+use serde_json::{json, Value};
+
+/// Tasks as `task export` writes them, minus `id` and `urgency`.
+fn tasks() -> Vec<Value> {
+    vec![
+        json!({
+            "uuid": "5c9f1a2e-1111-4a5b-9c1d-000000000001",
+            "description": "Renew passport",
+            "status": "pending",
+            "entry": "20261001T080000Z",
+            "modified": "20261010T090000Z",
+            "due": "20261019T220000Z",
+            "priority": "H",
+            "tags": ["errand", "town"],
+            "project": "travel",
+            "annotations": [{ "entry": "20261002T100000Z", "description": "Need photos" }],
+            "estimate": "2h",
+        }),
+        json!({
+            "uuid": "5c9f1a2e-1111-4a5b-9c1d-000000000002",
+            "description": "Write report",
+            "status": "pending",
+            "entry": "20261003T080000Z",
+            "modified": "20261014T080000Z",
+            "start": "20261014T080000Z",
+            "depends": ["5c9f1a2e-1111-4a5b-9c1d-000000000001"],
+        }),
+        json!({
+            "uuid": "5c9f1a2e-1111-4a5b-9c1d-000000000003",
+            "description": "Pay rent",
+            "status": "completed",
+            "entry": "20260901T080000Z",
+            "modified": "20261001T120000Z",
+            "end": "20261001T120000Z",
+            "priority": "L",
+        }),
+        json!({
+            "uuid": "5c9f1a2e-1111-4a5b-9c1d-000000000004",
+            "description": "Old idea",
+            "status": "deleted",
+            "entry": "20260901T080000Z",
+            "modified": "20260915T120000Z",
+            "end": "20260915T120000Z",
+        }),
+        json!({
+            "uuid": "5c9f1a2e-1111-4a5b-9c1d-000000000005",
+            "description": "Water plants",
+            "status": "recurring",
+            "entry": "20260901T080000Z",
+            "modified": "20260901T080000Z",
+            "due": "20261016T000000Z",
+            "recur": "weekly",
+            "mask": "--",
+            "priority": "Urgent",
+        }),
+    ]
+}
+
+fn time(s: &str) -> Option<Timestamp> {
+    Some(s.parse().unwrap())
+}
+
+#[test]
+fn fields_are_mapped() {
+    let text = serde_json::to_string(&tasks()).unwrap();
+    let todos = taskwarrior::parse(&text).unwrap();
+
+    let passport = &todos[0];
+    assert_eq!(passport.description, "Renew passport");
+    assert_eq!(passport.status, Status::Todo);
+    assert_eq!(passport.priority, Some(Priority::High));
+    assert_eq!(passport.due, Some("2026-10-19".parse::<Date>().unwrap()));
+    assert_eq!(passport.tags, ["errand", "town"]);
+    assert_eq!(passport.project.as_deref(), Some("travel"));
+    assert_eq!(passport.created_at, time("2026-10-01T08:00:00Z"));
+    assert_eq!(passport.updated_at, time("2026-10-10T09:00:00Z"));
+    assert_eq!(passport.extra["annotations"][0]["description"], "Need photos");
+    assert_eq!(passport.extra["uuid"], "5c9f1a2e-1111-4a5b-9c1d-000000000001");
+
+    assert_eq!(todos[1].status, Status::InProgress);
+    assert_eq!(todos[2].status, Status::Done);
+    assert_eq!(todos[2].completed_at, time("2026-10-01T12:00:00Z"));
+    assert_eq!(todos[3].status, Status::Cancelled);
+    assert_eq!(todos[3].completed_at, None);
+    assert_eq!((todos[4].status, todos[4].priority), (Status::Todo, None));
+}
+
+#[test]
+fn tasks_round_trip() {
+    let text = serde_json::to_string(&tasks()).unwrap();
+    let exported: Value = serde_json::from_str(&taskwarrior::format(&taskwarrior::parse(&text).unwrap())).unwrap();
+    assert_eq!(exported, Value::from(tasks()));
+}
+
+#[test]
+fn todos_round_trip() {
+    let mut full = Todo::new("Plan offsite");
+    full.id = 7;
+    full.priority = Some(Priority::Medium);
+    full.due = Some("2026-11-01".parse().unwrap());
+    full.tags = vec!["work".to_string()];
+    full.project = Some("team".to_string());
+    full.created_at = time("2026-10-01T08:00:00Z");
+    full.updated_at = time("2026-10-15T09:30:00Z");
+
+    for status in Status::ALL {
+        let mut todo = full.clone();
+        todo.status = status;
+        if status == Status::Done {
+            todo.completed_at = todo.updated_at;
+        }
+        let text = taskwarrior::format(&[todo.clone()]);
+        let back = taskwarrior::parse(&text).unwrap().remove(0);
+        // The generated UUID comes back, and so do the `start` and `end`
+        // times Taskwarrior needs for some statuses
+        assert_eq!(back.extra["uuid"], taskwarrior::uuid(&todo).as_str());
+        let mut merged = taskwarrior::merge(&todo, &back);
+        merged.extra.retain(|key, _| key != "start" && key != "end");
+        assert_eq!(merged, todo, "through {}", text);
+    }
+}
+
+#[test]
+fn invalid_tasks_are_rejected_with_their_position() {
+    let error = taskwarrior::parse("{\"description\":\"Fine\"}\n{\"description\":\"Odd\",\"status\":\"bogus\"}\n");
+    assert_eq!(error.unwrap_err().to_string(), "task 2: unknown status `bogus`");
+    assert!(taskwarrior::parse("[{\"status\":\"pending\"}]").is_err());
+}
@@ -0,0 +1,150 @@
+//! Round-trip tests for the todo.txt import and export: nothing a todo.txt
+//! line or a `Todo` holds may be dropped on the way through the other.
+
+use cli_todo_rust::{todotxt, Date, Priority, Status, Timestamp, Todo};
+use serde_json::Value;
+
+/// Lines already in the order `format_line` writes tokens in, so reading
+/// and writing them again must give the exact same text.
+const LINES: &[&str] = &[
+    "Plain task",
+    "(A) Call the landlord +home @phone",
+    "(B) 2026-10-01 Pay rent +home @online due:2026-10-20",
+    "(C) 2026-09-30 Water plants",
+    "(F) Someday, maybe",
+    "x Done without dates",
+    "x 2026-10-15 Done with a completion date",
+    "x 2026-10-15 2026-10-01 Done with both dates +home @online pri:A",
+    "x 2026-10-15 Low-priority chore pri:Q",
+    "x Cancelled errand status:cancelled created:2026-10-02",
+    "2026-10-03 Write report +work @desk @office status:in-progress",
+    "Waiting on review +work status:blocked",
+    "Meet at 10:30 see https://example.com and +other +work",
+    "Unknown extensions +work est:3h rec:+1w thread:123",
+];
+
+fn date(s: &str) -> Date {
+    s.parse().unwrap()
+}
+
+#[test]
+fn lines_round_trip_exactly() {
+    for line in LINES {
+        let todo = todotxt::parse_line(line).unwrap_or_else(|e| panic!("{}: {}", line, e));
+        assert_eq!(todotxt::format_line(&todo), *line);
+    }
+}
+
+#[test]
+fn whole_files_round_trip() {
+    let file: String = LINES.iter().map(|line| format!("{}\n", line)).collect();
+    let todos = todotxt::parse(&file).unwrap();
+    assert_eq!(todos.len(), LINES.len());
+    assert_eq!(todotxt::format(&todos), file);
+    assert_eq!(todotxt::parse(&todotxt::format(&todos)).unwrap(), todos);
+}
+
+#[test]
+fn fields_are_mapped() {
+    let todo = todotxt::parse_line("(B) 2026-10-01 Pay rent @online +home due:2026-10-20 est:1h").unwrap();
+    assert_eq!(todo.description, "Pay rent");
+    assert_eq!(todo.status, Status::Todo);
+    assert_eq!(todo.priority, Some(Priority::Medium));
+    assert_eq!(todo.created_at, Some(Timestamp::start_of(date("2026-10-01"))));
+    assert_eq!(todo.completed_at, None);
+    assert_eq!(todo.project.as_deref(), Some("home"));
+    assert_eq!(todo.tags, ["online"]);
+    assert_eq!(todo.due, Some(date("2026-10-20")));
+    assert_eq!(todo.extra.get("est"), Some(&Value::from("1h")));
+
+    let done = todotxt::parse_line("x 2026-10-15 2026-10-01 Pay rent pri:C").unwrap();
+    assert_eq!(done.status, Status::Done);
+    assert_eq!(done.priority, Some(Priority::Low));
+    assert_eq!(done.completed_at, Some(Timestamp::start_of(date("2026-10-15"))));
+    assert_eq!(done.created_at, Some(Timestamp::start_of(date("2026-10-01"))));
+
+    // Only the last +project is the project; the others stay in the text
+    let todo = todotxt::parse_line("Plan +q4 offsite +work").unwrap();
+    assert_eq!(todo.description, "Plan +q4 offsite");
+    assert_eq!(todo.project.as_deref(), Some("work"));
+}
+
+#[test]
+fn todos_round_trip() {
+    let day = |s: &str| Some(Timestamp::start_of(date(s)));
+    let mut full = Todo::new("Renew passport");
+    full.priority = Some(Priority::High);
+    full.due = Some(date("2026-11-01"));
+    full.tags = vec!["errand".to_string(), "town".to_string()];
+    full.project = Some("travel".to_string());
+    full.created_at = day("2026-10-01");
+    full.extra.insert("est".to_string(), Value::from("2h"));
+
+    let mut todos = vec![full.clone()];
+    for status in Status::ALL {
+        let mut todo = full.clone();
+        todo.status = status;
+        if status == Status::Done {
+            todo.completed_at = day("2026-10-15");
+        }
+        todos.push(todo);
+    }
+    // Unset fields must stay unset, including a closed task's dates
+    todos.push(Todo::new("Bare"));
+    todos.push(Todo { status: Status::Done, ..Todo::new("Done, dates unknown") });
+    todos.push(Todo { status: Status::Done, created_at: day("2026-10-01"), ..Todo::new("Done, created") });
+    let mut low = Todo::new("Low and done");
+    low.status = Status::Done;
+    low.priority = Some(Priority::Low);
+    todos.push(low);
+
+    for todo in &todos {
+        let line = todotxt::format_line(todo);
+        let back = todotxt::parse_line(&line).unwrap_or_else(|e| panic!("{}: {}", line, e));
+        assert_eq!(&back, todo, "through `{}`", line);
+    }
+}
+
+#[test]
+fn descriptions_that_look_like_tokens_round_trip() {
+    let day = |s: &str| Some(Timestamp::start_of(date(s)));
+    let descriptions = [
+        "Read chapter:2 notes",
+        "due:tomorrow or status:done",
+        "x marks the spot",
+        "(A) grade essays",
+        "2026-10-01 was a Thursday",
+        "Email @bob about +q4 and +q1",
+        r"Clean C:\temp and \ this",
+    ];
+    for description in descriptions {
+        let plain = Todo::new(description);
+        let mut variants = vec![plain.clone(), Todo { project: Some("home".to_string()), ..plain.clone() }];
+        for status in Status::ALL {
+            variants.push(Todo { status, ..plain.clone() });
+            variants.push(Todo { status, priority: Some(Priority::High), ..plain.clone() });
+            variants.push(Todo { status, created_at: day("2026-10-01"), ..plain.clone() });
+            if status == Status::Done {
+                variants.push(Todo { status, completed_at: day("2026-10-15"), ..plain.clone() });
+            }
+        }
+        for todo in &variants {
+            let line = todotxt::format_line(todo);
+            let back = todotxt::parse_line(&line).unwrap_or_else(|e| panic!("{}: {}", line, e));
+            assert_eq!(&back, todo, "through `{}`", line);
+        }
+    }
+
+    // Escaped words read back exactly
+    for line in [r"Read \chapter:2 notes", r"\x marks \@bob", r"(B) \2026-10-01 review \\n"] {
+        assert_eq!(todotxt::format_line(&todotxt::parse_line(line).unwrap()), line);
+    }
+    assert_eq!(todotxt::parse_line(r"\(A) grade \+q4 +home").unwrap().description, "(A) grade +q4");
+}
+
+#[test]
+fn invalid_lines_are_rejected_with_their_line_number() {
+    let error = todotxt::parse("Fine\n\n+project-only @context\n").unwrap_err();
+    assert_eq!(error.to_string(), "line 3: the description must not be empty");
+    assert!(todotxt::parse("(A) 2026-02-30 Impossible date\n").is_err());
+}